@@ -1,11 +1,17 @@
+use crate::error::LoxError;
 use crate::token::Token;
+use std::cell::{Cell, RefCell};
 use std::fmt::Display;
-use std::sync::Arc;
+use std::rc::Rc;
 
+use crate::compile;
 use crate::interpret::{self};
+use crate::resolve;
 
 pub enum AstNodeKind {
     BinaryExpr,
+    ConditionalExpr,
+    CoalesceExpr,
     UnaryExpr,
     GroupExpr,
     LiteralExpr(Token),
@@ -16,30 +22,63 @@ pub enum AstNodeKind {
     Program,
     IfStmt,
     WhileStmt,
+    ForStmt,
     AssignExpr,
     BreakStmt,
+    ContinueStmt,
     ReturnStmt,
     FunCall,
     FunDecl,
+    ClassDecl,
+    GetExpr(AstNodeRef, Token),
+    SetExpr,
+    SuperExpr,
+    ListExpr,
+    MapExpr,
+    InterpolationExpr,
+    IndexExpr(AstNodeRef, Token, AstNodeRef),
+    IndexSetExpr,
+    ThrowStmt,
+    TryStmt,
+    ImportStmt,
 }
 
 pub trait AstNode: Display {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()>;
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError>;
+    fn resolve(&self, resolver: &mut resolve::Resolver);
+    fn compile(&self, compiler: &mut compile::Compiler);
     fn kind(&self) -> AstNodeKind;
 }
-pub type AstNodeRef = Arc<dyn AstNode>;
+pub type AstNodeRef = Rc<dyn AstNode>;
 
 pub struct BinaryExpr {
     token: Token,
     rexpr: AstNodeRef,
     lexpr: AstNodeRef,
 }
+/// `condition ? then : otherwise`
+pub struct ConditionalExpr {
+    token: Token,
+    condition: AstNodeRef,
+    then: AstNodeRef,
+    otherwise: AstNodeRef,
+}
+/// `lexpr ?? rexpr`, the right side only runs when the left one is nil.
+pub struct CoalesceExpr {
+    token: Token,
+    lexpr: AstNodeRef,
+    rexpr: AstNodeRef,
+}
 pub struct UnaryExpr {
     token: Token,
     expr: AstNodeRef,
 }
 pub struct LiteralExpr {
     token: Token,
+    depth: Cell<Option<usize>>,
 }
 pub struct GroupExpr {
     expr: AstNodeRef,
@@ -48,6 +87,7 @@ pub struct ExprStmt {
     expr: AstNodeRef,
 }
 pub struct PrintStmt {
+    token: Token,
     expr: AstNodeRef,
 }
 pub struct VarDecl {
@@ -57,12 +97,14 @@ pub struct VarDecl {
 pub struct AssignExpr {
     variable: Token,
     expr: AstNodeRef,
+    depth: Cell<Option<usize>>,
 }
 pub struct Program {
     decs: Vec<AstNodeRef>,
 }
 pub struct Block {
     decs: Vec<AstNodeRef>,
+    captured: RefCell<Vec<String>>,
 }
 pub struct IfStmt {
     expr: AstNodeRef,
@@ -70,18 +112,51 @@ pub struct IfStmt {
     elstmt: Option<AstNodeRef>,
 }
 pub struct WhileStmt {
+    label: Option<Token>,
     expr: AstNodeRef,
     stmt: AstNodeRef,
 }
+/// A for loop, kept apart from while loops so `continue` still runs the
+/// increment.
+pub struct ForStmt {
+    label: Option<Token>,
+    keyword: Token,
+    initialize: Option<AstNodeRef>,
+    condition: Option<AstNodeRef>,
+    increment: Option<AstNodeRef>,
+    stmt: AstNodeRef,
+    captured: RefCell<Vec<String>>,
+}
 pub struct BreakStmt {
     token: Token,
+    /// The loop to leave, the innermost one when `None`.
+    label: Option<Token>,
+}
+pub struct ContinueStmt {
+    token: Token,
+    label: Option<Token>,
 }
 pub struct ReturnStmt {
     token: Token,
     expr: Option<AstNodeRef>,
 }
+pub struct ThrowStmt {
+    token: Token,
+    expr: AstNodeRef,
+}
+pub struct TryStmt {
+    token: Token,
+    body: AstNodeRef,
+    catch: Option<(Token, AstNodeRef)>,
+    finally: Option<AstNodeRef>,
+    captured: RefCell<Vec<String>>,
+}
+pub struct ImportStmt {
+    path: Token,
+    name: Token,
+}
 pub struct FunCall {
-    line: usize,
+    paren: Token,
     callee: AstNodeRef,
     args: Vec<AstNodeRef>,
 }
@@ -89,19 +164,110 @@ pub struct FunDecl {
     name: Token,
     params: Vec<Token>,
     block: AstNodeRef,
+    captured: RefCell<Vec<String>>,
 }
 pub struct FunDef {
     params: Vec<Token>,
     block: AstNodeRef,
+    captured: RefCell<Vec<String>>,
+}
+pub struct ClassDecl {
+    name: Token,
+    superclass: Option<AstNodeRef>,
+    methods: Vec<Rc<FunDecl>>,
+}
+pub struct GetExpr {
+    object: AstNodeRef,
+    name: Token,
+}
+pub struct SetExpr {
+    object: AstNodeRef,
+    name: Token,
+    expr: AstNodeRef,
+}
+pub struct SuperExpr {
+    keyword: Token,
+    method: Token,
+    depth: Cell<Option<usize>>,
+}
+pub struct ListExpr {
+    bracket: Token,
+    items: Vec<AstNodeRef>,
+}
+pub struct MapExpr {
+    brace: Token,
+    entries: Vec<(AstNodeRef, AstNodeRef)>,
+}
+/// A string literal with `${...}` parts, `strings` surround `exprs`.
+pub struct InterpolationExpr {
+    token: Token,
+    strings: Vec<String>,
+    exprs: Vec<AstNodeRef>,
+}
+pub struct IndexExpr {
+    object: AstNodeRef,
+    bracket: Token,
+    index: AstNodeRef,
+}
+pub struct IndexSetExpr {
+    object: AstNodeRef,
+    bracket: Token,
+    index: AstNodeRef,
+    expr: AstNodeRef,
 }
 
 pub struct Ast {
     root: AstNodeRef,
 }
 
+impl ConditionalExpr {
+    pub fn create(
+        token: Token,
+        condition: AstNodeRef,
+        then: AstNodeRef,
+        otherwise: AstNodeRef,
+    ) -> AstNodeRef {
+        Rc::new(ConditionalExpr {
+            token,
+            condition,
+            then,
+            otherwise,
+        })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+    pub fn condition(&self) -> &AstNodeRef {
+        &self.condition
+    }
+    pub fn then(&self) -> &AstNodeRef {
+        &self.then
+    }
+    pub fn otherwise(&self) -> &AstNodeRef {
+        &self.otherwise
+    }
+}
+impl CoalesceExpr {
+    pub fn create(token: Token, lexpr: AstNodeRef, rexpr: AstNodeRef) -> AstNodeRef {
+        Rc::new(CoalesceExpr {
+            token,
+            lexpr,
+            rexpr,
+        })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+    pub fn lexpr(&self) -> &AstNodeRef {
+        &self.lexpr
+    }
+    pub fn rexpr(&self) -> &AstNodeRef {
+        &self.rexpr
+    }
+}
 impl BinaryExpr {
     pub fn create(token: Token, lexpr: AstNodeRef, rexpr: AstNodeRef) -> AstNodeRef {
-        Arc::new(BinaryExpr {
+        Rc::new(BinaryExpr {
             lexpr,
             rexpr,
             token,
@@ -119,7 +285,7 @@ impl BinaryExpr {
 }
 impl UnaryExpr {
     pub fn create(token: Token, expr: AstNodeRef) -> AstNodeRef {
-        Arc::new(UnaryExpr { expr, token })
+        Rc::new(UnaryExpr { expr, token })
     }
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
@@ -130,15 +296,24 @@ impl UnaryExpr {
 }
 impl LiteralExpr {
     pub fn create(token: Token) -> AstNodeRef {
-        Arc::new(LiteralExpr { token })
+        Rc::new(LiteralExpr {
+            token,
+            depth: Cell::new(None),
+        })
     }
     pub fn token(&self) -> &Token {
         &self.token
     }
+    pub fn depth(&self) -> Option<usize> {
+        self.depth.get()
+    }
+    pub fn set_depth(&self, depth: usize) {
+        self.depth.set(Some(depth))
+    }
 }
 impl GroupExpr {
     pub fn create(expr: AstNodeRef) -> AstNodeRef {
-        Arc::new(GroupExpr { expr })
+        Rc::new(GroupExpr { expr })
     }
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
@@ -146,7 +321,11 @@ impl GroupExpr {
 }
 impl AssignExpr {
     pub fn create(variable: Token, expr: AstNodeRef) -> AstNodeRef {
-        Arc::new(AssignExpr { variable, expr })
+        Rc::new(AssignExpr {
+            variable,
+            expr,
+            depth: Cell::new(None),
+        })
     }
     pub fn variable(&self) -> &Token {
         &self.variable
@@ -154,18 +333,27 @@ impl AssignExpr {
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
     }
+    pub fn depth(&self) -> Option<usize> {
+        self.depth.get()
+    }
+    pub fn set_depth(&self, depth: usize) {
+        self.depth.set(Some(depth))
+    }
 }
 impl ExprStmt {
     pub fn create(expr: AstNodeRef) -> AstNodeRef {
-        Arc::new(ExprStmt { expr })
+        Rc::new(ExprStmt { expr })
     }
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
     }
 }
 impl PrintStmt {
-    pub fn create(expr: AstNodeRef) -> AstNodeRef {
-        Arc::new(PrintStmt { expr })
+    pub fn create(token: Token, expr: AstNodeRef) -> AstNodeRef {
+        Rc::new(PrintStmt { token, expr })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
     }
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
@@ -173,7 +361,7 @@ impl PrintStmt {
 }
 impl VarDecl {
     pub fn create(variable: Token, expr: Option<AstNodeRef>) -> AstNodeRef {
-        Arc::new(VarDecl { variable, expr })
+        Rc::new(VarDecl { variable, expr })
     }
     pub fn expr(&self) -> Option<&AstNodeRef> {
         self.expr.as_ref()
@@ -184,7 +372,7 @@ impl VarDecl {
 }
 impl Program {
     pub fn create(stmts: Vec<AstNodeRef>) -> AstNodeRef {
-        Arc::new(Program { decs: stmts })
+        Rc::new(Program { decs: stmts })
     }
     pub fn decs(&self) -> &Vec<AstNodeRef> {
         &self.decs
@@ -192,15 +380,26 @@ impl Program {
 }
 impl Block {
     pub fn create(decs: Vec<AstNodeRef>) -> AstNodeRef {
-        Arc::new(Block { decs })
+        Rc::new(Block {
+            decs,
+            captured: RefCell::new(vec![]),
+        })
     }
     pub fn decs(&self) -> &Vec<AstNodeRef> {
         &self.decs
     }
+    /// Names declared in this scope that closures refer to, filled in by
+    /// the resolver.
+    pub fn captured(&self) -> Vec<String> {
+        self.captured.borrow().clone()
+    }
+    pub fn set_captured(&self, captured: Vec<String>) {
+        *self.captured.borrow_mut() = captured;
+    }
 }
 impl IfStmt {
     pub fn create(expr: AstNodeRef, stmt: AstNodeRef, elstmt: Option<AstNodeRef>) -> AstNodeRef {
-        Arc::new(IfStmt { expr, stmt, elstmt })
+        Rc::new(IfStmt { expr, stmt, elstmt })
     }
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
@@ -213,8 +412,11 @@ impl IfStmt {
     }
 }
 impl WhileStmt {
-    pub fn create(expr: AstNodeRef, stmt: AstNodeRef) -> AstNodeRef {
-        Arc::new(WhileStmt { expr, stmt })
+    pub fn create(label: Option<Token>, expr: AstNodeRef, stmt: AstNodeRef) -> AstNodeRef {
+        Rc::new(WhileStmt { label, expr, stmt })
+    }
+    pub fn label(&self) -> Option<&Token> {
+        self.label.as_ref()
     }
     pub fn expr(&self) -> &AstNodeRef {
         &self.expr
@@ -223,17 +425,77 @@ impl WhileStmt {
         &self.stmt
     }
 }
+impl ForStmt {
+    pub fn create(
+        label: Option<Token>,
+        keyword: Token,
+        initialize: Option<AstNodeRef>,
+        condition: Option<AstNodeRef>,
+        increment: Option<AstNodeRef>,
+        stmt: AstNodeRef,
+    ) -> AstNodeRef {
+        Rc::new(ForStmt {
+            label,
+            keyword,
+            initialize,
+            condition,
+            increment,
+            stmt,
+            captured: RefCell::new(vec![]),
+        })
+    }
+    pub fn label(&self) -> Option<&Token> {
+        self.label.as_ref()
+    }
+    pub fn keyword(&self) -> &Token {
+        &self.keyword
+    }
+    pub fn initialize(&self) -> Option<&AstNodeRef> {
+        self.initialize.as_ref()
+    }
+    pub fn condition(&self) -> Option<&AstNodeRef> {
+        self.condition.as_ref()
+    }
+    pub fn increment(&self) -> Option<&AstNodeRef> {
+        self.increment.as_ref()
+    }
+    pub fn stmt(&self) -> &AstNodeRef {
+        &self.stmt
+    }
+    /// Names of the loop scope, the one of its initializer, that closures
+    /// refer to.
+    pub fn captured(&self) -> Vec<String> {
+        self.captured.borrow().clone()
+    }
+    pub fn set_captured(&self, captured: Vec<String>) {
+        *self.captured.borrow_mut() = captured;
+    }
+}
+impl ContinueStmt {
+    pub fn create(token: Token, label: Option<Token>) -> AstNodeRef {
+        Rc::new(ContinueStmt { token, label })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+    pub fn label(&self) -> Option<&Token> {
+        self.label.as_ref()
+    }
+}
 impl BreakStmt {
-    pub fn create(token: Token) -> AstNodeRef {
-        Arc::new(BreakStmt { token })
+    pub fn create(token: Token, label: Option<Token>) -> AstNodeRef {
+        Rc::new(BreakStmt { token, label })
     }
     pub fn token(&self) -> &Token {
         &self.token
     }
+    pub fn label(&self) -> Option<&Token> {
+        self.label.as_ref()
+    }
 }
 impl ReturnStmt {
     pub fn create(token: Token, expr: Option<AstNodeRef>) -> AstNodeRef {
-        Arc::new(ReturnStmt { token, expr })
+        Rc::new(ReturnStmt { token, expr })
     }
     pub fn token(&self) -> &Token {
         &self.token
@@ -242,9 +504,74 @@ impl ReturnStmt {
         self.expr.as_ref()
     }
 }
+impl ThrowStmt {
+    pub fn create(token: Token, expr: AstNodeRef) -> AstNodeRef {
+        Rc::new(ThrowStmt { token, expr })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+    pub fn expr(&self) -> &AstNodeRef {
+        &self.expr
+    }
+}
+impl TryStmt {
+    pub fn create(
+        token: Token,
+        body: AstNodeRef,
+        catch: Option<(Token, AstNodeRef)>,
+        finally: Option<AstNodeRef>,
+    ) -> AstNodeRef {
+        Rc::new(TryStmt {
+            token,
+            body,
+            catch,
+            finally,
+            captured: RefCell::new(vec![]),
+        })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+    pub fn body(&self) -> &AstNodeRef {
+        &self.body
+    }
+    /// The name bound to the caught value and the block handling it.
+    pub fn catch(&self) -> Option<&(Token, AstNodeRef)> {
+        self.catch.as_ref()
+    }
+    pub fn finally(&self) -> Option<&AstNodeRef> {
+        self.finally.as_ref()
+    }
+    /// The name bound by `catch` when closures refer to it, empty
+    /// otherwise. Filled in by the resolver.
+    pub fn captured(&self) -> Vec<String> {
+        self.captured.borrow().clone()
+    }
+    pub fn set_captured(&self, captured: Vec<String>) {
+        *self.captured.borrow_mut() = captured;
+    }
+}
+impl ImportStmt {
+    pub fn create(path: Token, name: Token) -> AstNodeRef {
+        Rc::new(ImportStmt { path, name })
+    }
+    /// The string literal naming the imported file.
+    pub fn path(&self) -> &Token {
+        &self.path
+    }
+    /// The name the module is bound to.
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+}
 impl FunCall {
-    pub fn create(callee: AstNodeRef, args: Vec<AstNodeRef>, line: usize) -> AstNodeRef {
-        Arc::new(FunCall { callee, args, line })
+    pub fn create(callee: AstNodeRef, args: Vec<AstNodeRef>, paren: Token) -> AstNodeRef {
+        Rc::new(FunCall {
+            callee,
+            args,
+            paren,
+        })
     }
     pub fn callee(&self) -> &AstNodeRef {
         &self.callee
@@ -252,16 +579,21 @@ impl FunCall {
     pub fn args(&self) -> &Vec<AstNodeRef> {
         &self.args
     }
-    pub fn line(&self) -> usize {
-        self.line
+    pub fn paren(&self) -> &Token {
+        &self.paren
     }
 }
 impl FunDecl {
     pub fn create(name: Token, args: Vec<Token>, block: AstNodeRef) -> AstNodeRef {
-        Arc::new(FunDecl {
+        Self::create_method(name, args, block)
+    }
+
+    pub fn create_method(name: Token, args: Vec<Token>, block: AstNodeRef) -> Rc<FunDecl> {
+        Rc::new(FunDecl {
             name,
             params: args,
             block,
+            captured: RefCell::new(vec![]),
         })
     }
 
@@ -276,13 +608,23 @@ impl FunDecl {
     pub fn block(&self) -> &AstNodeRef {
         &self.block
     }
+
+    /// Parameters that closures refer to, filled in by the resolver.
+    pub fn captured(&self) -> Vec<String> {
+        self.captured.borrow().clone()
+    }
+
+    pub fn set_captured(&self, captured: Vec<String>) {
+        *self.captured.borrow_mut() = captured;
+    }
 }
 
 impl FunDef {
     pub fn create(args: Vec<Token>, block: AstNodeRef) -> AstNodeRef {
-        Arc::new(FunDef {
+        Rc::new(FunDef {
             params: args,
             block,
+            captured: RefCell::new(vec![]),
         })
     }
 
@@ -293,6 +635,169 @@ impl FunDef {
     pub fn block(&self) -> &AstNodeRef {
         &self.block
     }
+
+    /// Parameters that closures refer to, filled in by the resolver.
+    pub fn captured(&self) -> Vec<String> {
+        self.captured.borrow().clone()
+    }
+
+    pub fn set_captured(&self, captured: Vec<String>) {
+        *self.captured.borrow_mut() = captured;
+    }
+}
+
+impl ClassDecl {
+    pub fn create(
+        name: Token,
+        superclass: Option<AstNodeRef>,
+        methods: Vec<Rc<FunDecl>>,
+    ) -> AstNodeRef {
+        Rc::new(ClassDecl {
+            name,
+            superclass,
+            methods,
+        })
+    }
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+    pub fn superclass(&self) -> Option<&AstNodeRef> {
+        self.superclass.as_ref()
+    }
+    pub fn methods(&self) -> &Vec<Rc<FunDecl>> {
+        &self.methods
+    }
+}
+impl GetExpr {
+    pub fn create(object: AstNodeRef, name: Token) -> AstNodeRef {
+        Rc::new(GetExpr { object, name })
+    }
+    pub fn object(&self) -> &AstNodeRef {
+        &self.object
+    }
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+}
+impl SetExpr {
+    pub fn create(object: AstNodeRef, name: Token, expr: AstNodeRef) -> AstNodeRef {
+        Rc::new(SetExpr { object, name, expr })
+    }
+    pub fn object(&self) -> &AstNodeRef {
+        &self.object
+    }
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+    pub fn expr(&self) -> &AstNodeRef {
+        &self.expr
+    }
+}
+impl SuperExpr {
+    pub fn create(keyword: Token, method: Token) -> AstNodeRef {
+        Rc::new(SuperExpr {
+            keyword,
+            method,
+            depth: Cell::new(None),
+        })
+    }
+    pub fn keyword(&self) -> &Token {
+        &self.keyword
+    }
+    pub fn method(&self) -> &Token {
+        &self.method
+    }
+    pub fn depth(&self) -> Option<usize> {
+        self.depth.get()
+    }
+    pub fn set_depth(&self, depth: usize) {
+        self.depth.set(Some(depth))
+    }
+}
+impl ListExpr {
+    pub fn create(bracket: Token, items: Vec<AstNodeRef>) -> AstNodeRef {
+        Rc::new(ListExpr { bracket, items })
+    }
+    pub fn bracket(&self) -> &Token {
+        &self.bracket
+    }
+    pub fn items(&self) -> &Vec<AstNodeRef> {
+        &self.items
+    }
+}
+impl MapExpr {
+    pub fn create(brace: Token, entries: Vec<(AstNodeRef, AstNodeRef)>) -> AstNodeRef {
+        Rc::new(MapExpr { brace, entries })
+    }
+    pub fn brace(&self) -> &Token {
+        &self.brace
+    }
+    pub fn entries(&self) -> &Vec<(AstNodeRef, AstNodeRef)> {
+        &self.entries
+    }
+}
+impl InterpolationExpr {
+    pub fn create(token: Token, strings: Vec<String>, exprs: Vec<AstNodeRef>) -> AstNodeRef {
+        Rc::new(InterpolationExpr {
+            token,
+            strings,
+            exprs,
+        })
+    }
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+    pub fn strings(&self) -> &Vec<String> {
+        &self.strings
+    }
+    pub fn exprs(&self) -> &Vec<AstNodeRef> {
+        &self.exprs
+    }
+}
+impl IndexExpr {
+    pub fn create(object: AstNodeRef, bracket: Token, index: AstNodeRef) -> AstNodeRef {
+        Rc::new(IndexExpr {
+            object,
+            bracket,
+            index,
+        })
+    }
+    pub fn object(&self) -> &AstNodeRef {
+        &self.object
+    }
+    pub fn bracket(&self) -> &Token {
+        &self.bracket
+    }
+    pub fn index(&self) -> &AstNodeRef {
+        &self.index
+    }
+}
+impl IndexSetExpr {
+    pub fn create(
+        object: AstNodeRef,
+        bracket: Token,
+        index: AstNodeRef,
+        expr: AstNodeRef,
+    ) -> AstNodeRef {
+        Rc::new(IndexSetExpr {
+            object,
+            bracket,
+            index,
+            expr,
+        })
+    }
+    pub fn object(&self) -> &AstNodeRef {
+        &self.object
+    }
+    pub fn bracket(&self) -> &Token {
+        &self.bracket
+    }
+    pub fn index(&self) -> &AstNodeRef {
+        &self.index
+    }
+    pub fn expr(&self) -> &AstNodeRef {
+        &self.expr
+    }
 }
 
 impl Ast {
@@ -309,6 +814,16 @@ impl Display for BinaryExpr {
         write!(f, "({} {} {})", self.token, self.lexpr, self.rexpr)
     }
 }
+impl Display for ConditionalExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(? {} {} {})", self.condition, self.then, self.otherwise)
+    }
+}
+impl Display for CoalesceExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(?? {} {})", self.lexpr, self.rexpr)
+    }
+}
 impl Display for UnaryExpr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({} {})", self.token, self.expr)
@@ -351,7 +866,7 @@ impl Display for Block {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let _ = write!(f, "{{");
         for s in self.decs.iter() {
-            write!(f, "{}\n", s)?;
+            writeln!(f, "{}", s)?;
         }
         write!(f, "}}")
     }
@@ -359,19 +874,49 @@ impl Display for Block {
 impl Display for IfStmt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.elstmt() {
-            Some(el) => write!(f, "(if {} => {} | {})\n", self.expr, self.stmt, el),
-            None => write!(f, "(if {} => {})\n", self.expr, self.stmt),
+            Some(el) => writeln!(f, "(if {} => {} | {})", self.expr, self.stmt, el),
+            None => writeln!(f, "(if {} => {})", self.expr, self.stmt),
         }
     }
 }
 impl Display for WhileStmt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(while {} => {})\n", self.expr, self.stmt)
+        if let Some(label) = &self.label {
+            write!(f, "{}: ", label)?;
+        }
+        writeln!(f, "(while {} => {})", self.expr, self.stmt)
+    }
+}
+impl Display for ForStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let part = |p: Option<&AstNodeRef>| p.map(|p| p.to_string()).unwrap_or_default();
+        if let Some(label) = &self.label {
+            write!(f, "{}: ", label)?;
+        }
+        writeln!(
+            f,
+            "(for {} {}; {} => {})",
+            part(self.initialize()),
+            part(self.condition()),
+            part(self.increment()),
+            self.stmt
+        )
     }
 }
 impl Display for BreakStmt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "break")
+        match &self.label {
+            Some(label) => write!(f, "break {}", label),
+            None => write!(f, "break"),
+        }
+    }
+}
+impl Display for ContinueStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "continue {}", label),
+            None => write!(f, "continue"),
+        }
     }
 }
 impl Display for ReturnStmt {
@@ -382,6 +927,28 @@ impl Display for ReturnStmt {
         }
     }
 }
+impl Display for ThrowStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(throw {})", self.expr)
+    }
+}
+impl Display for TryStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(try {}", self.body)?;
+        if let Some((param, block)) = &self.catch {
+            write!(f, " catch {} => {}", param, block)?;
+        }
+        if let Some(finally) = &self.finally {
+            write!(f, " finally {}", finally)?;
+        }
+        writeln!(f, ")")
+    }
+}
+impl Display for ImportStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(import {} as {})", self.path, self.name)
+    }
+}
 impl Display for FunCall {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}", self.callee)?;
@@ -411,10 +978,80 @@ impl Display for FunDef {
         write!(f, "{}", self.block())
     }
 }
+impl Display for ClassDecl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(class {} ", self.name)?;
+        if let Some(s) = self.superclass() {
+            write!(f, "< {} ", s)?;
+        }
+        for m in self.methods.iter() {
+            write!(f, "{}", m)?;
+        }
+        write!(f, ")")
+    }
+}
+impl Display for GetExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}.{})", self.object, self.name)
+    }
+}
+impl Display for SetExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}.{}={})", self.object, self.name, self.expr)
+    }
+}
+impl Display for SuperExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(super.{})", self.method)
+    }
+}
+impl Display for ListExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        write!(f, "]")
+    }
+}
+impl Display for InterpolationExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}", self.strings[0])?;
+        for (expr, string) in self.exprs.iter().zip(&self.strings[1..]) {
+            write!(f, "${{{}}}{}", expr, string)?;
+        }
+        write!(f, "\"")
+    }
+}
+impl Display for MapExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", key, value)?;
+        }
+        write!(f, "}}")
+    }
+}
+impl Display for IndexExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}[{}])", self.object, self.index)
+    }
+}
+impl Display for IndexSetExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}[{}]={})", self.object, self.index, self.expr)
+    }
+}
 impl Display for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for s in self.decs.iter() {
-            write!(f, "{}\n", s)?;
+            writeln!(f, "{}", s)?;
         }
         Ok(())
     }
@@ -427,138 +1064,567 @@ impl Display for Ast {
 }
 
 impl AstNode for BinaryExpr {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_binary(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_binary(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_binary(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::BinaryExpr
     }
 }
+impl AstNode for ConditionalExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_conditional(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_conditional(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_conditional(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ConditionalExpr
+    }
+}
+impl AstNode for CoalesceExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_coalesce(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_coalesce(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_coalesce(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::CoalesceExpr
+    }
+}
 impl AstNode for UnaryExpr {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_unary(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_unary(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_unary(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::UnaryExpr
     }
 }
 impl AstNode for GroupExpr {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_group(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_group(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_group(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::GroupExpr
     }
 }
 impl AstNode for AssignExpr {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_assignment(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_assignment(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_assignment(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::AssignExpr
     }
 }
 impl AstNode for LiteralExpr {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_literal(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_literal(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_literal(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::LiteralExpr(self.token.clone())
     }
 }
 impl AstNode for ExprStmt {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_expr_stmt(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_expr_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_expr_stmt(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::ExprStmt
     }
 }
 impl AstNode for PrintStmt {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_print_stmt(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_print_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_print_stmt(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::PrintStmt
     }
 }
 impl AstNode for VarDecl {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_var_decl(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_var_decl(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_var_decl(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::VarDecl
     }
 }
 impl AstNode for IfStmt {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_if_stmt(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_if_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_if_stmt(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::IfStmt
     }
 }
 impl AstNode for WhileStmt {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_while_stmt(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_while_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_while_stmt(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::WhileStmt
     }
 }
+impl AstNode for ForStmt {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_for_stmt(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_for_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_for_stmt(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ForStmt
+    }
+}
+impl AstNode for ContinueStmt {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_continue_stmt(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_continue_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_continue_stmt(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ContinueStmt
+    }
+}
 impl AstNode for BreakStmt {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_break_stmt(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_break_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_break_stmt(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::BreakStmt
     }
 }
 impl AstNode for ReturnStmt {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_return_stmt(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_return_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_return_stmt(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::ReturnStmt
     }
 }
+impl AstNode for ThrowStmt {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_throw_stmt(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_throw_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_throw_stmt(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ThrowStmt
+    }
+}
+impl AstNode for TryStmt {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_try_stmt(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_try_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_try_stmt(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::TryStmt
+    }
+}
+impl AstNode for ImportStmt {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_import_stmt(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_import_stmt(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_import_stmt(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ImportStmt
+    }
+}
 impl AstNode for FunCall {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_fun_call(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_fun_call(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_fun_call(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::FunCall
     }
 }
 impl AstNode for FunDecl {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_fun_decl(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_fun_decl(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_fun_decl(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::FunDecl
     }
 }
 impl AstNode for FunDef {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_fun_def(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_fun_def(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_fun_def(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::FunDecl
     }
 }
+impl AstNode for ClassDecl {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_class_decl(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_class_decl(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_class_decl(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ClassDecl
+    }
+}
+impl AstNode for GetExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_get_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_get_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_get_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::GetExpr(self.object.clone(), self.name.clone())
+    }
+}
+impl AstNode for SetExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_set_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_set_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_set_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::SetExpr
+    }
+}
+impl AstNode for SuperExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_super_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_super_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_super_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::SuperExpr
+    }
+}
 impl AstNode for Program {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_program(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_program(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_program(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::Program
     }
 }
 impl AstNode for Block {
-    fn interpret(&self, interpretor: &mut interpret::Interpretor) -> Result<interpret::Value, ()> {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
         interpretor.interpret_block(self)
     }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_block(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_block(self)
+    }
     fn kind(&self) -> AstNodeKind {
         AstNodeKind::Block
     }
 }
+impl AstNode for ListExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_list_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_list_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_list_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::ListExpr
+    }
+}
+impl AstNode for MapExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_map_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_map_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_map_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::MapExpr
+    }
+}
+impl AstNode for InterpolationExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_interpolation_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_interpolation_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_interpolation_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::InterpolationExpr
+    }
+}
+impl AstNode for IndexExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_index_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_index_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_index_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::IndexExpr(
+            self.object.clone(),
+            self.bracket.clone(),
+            self.index.clone(),
+        )
+    }
+}
+impl AstNode for IndexSetExpr {
+    fn interpret(
+        &self,
+        interpretor: &mut interpret::Interpretor,
+    ) -> Result<interpret::Value, LoxError> {
+        interpretor.interpret_index_set_expr(self)
+    }
+    fn resolve(&self, resolver: &mut resolve::Resolver) {
+        resolver.resolve_index_set_expr(self)
+    }
+    fn compile(&self, compiler: &mut compile::Compiler) {
+        compiler.compile_index_set_expr(self)
+    }
+    fn kind(&self) -> AstNodeKind {
+        AstNodeKind::IndexSetExpr
+    }
+}
@@ -0,0 +1,180 @@
+use std::rc::Rc;
+
+use crate::{interpret::Value, token::Token};
+
+/// A single instruction of the virtual machine. Operands are indices into
+/// the pools of the chunk holding the instruction, stack slots relative to
+/// the current call frame or absolute jump targets.
+#[derive(Clone, Copy, Debug)]
+pub enum OpCode {
+    Constant(u32),
+    Nil,
+    True,
+    False,
+    Pop,
+    PopN(u32),
+    Dup,
+    GetLocal(u32),
+    SetLocal(u32),
+    /// Reads a name from the environment `depth` levels up the chain.
+    GetEnv(u16, u32),
+    SetEnv(u16, u32),
+    DefineEnv(u32),
+    GetGlobal(u32),
+    SetGlobal(u32),
+    DefineGlobal(u32),
+    PushEnv,
+    PopEnv,
+    GetProperty(u32),
+    /// Fails unless the value on top of the stack is an instance, so
+    /// the right hand side of a field assignment runs only on instances.
+    ExpectInstance,
+    SetProperty(u32),
+    GetSuper(u32),
+    /// Builds a list out of the given number of items on the stack.
+    List(u32),
+    /// Builds a map out of the given number of key, value pairs.
+    Map(u32),
+    /// Joins the given number of values on the stack into a string, each
+    /// as it is displayed.
+    Concat(u32),
+    GetIndex,
+    SetIndex,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump(u32),
+    JumpIfFalse(u32),
+    Call(u32),
+    Closure(u32),
+    Inherit,
+    /// Builds a class out of the given number of methods on the stack,
+    /// preceded by the superclass when the flag is set.
+    Class(u32, u16, bool),
+    Return,
+    /// Installs a handler for runtime errors at the target. A finally
+    /// handler keeps the error to raise it again, the others push the
+    /// value `catch` receives.
+    Try(u32, bool),
+    EndTry,
+    Throw,
+    /// Raises the error kept by the innermost finally handler again.
+    Rethrow,
+    /// Forgets that error, when a finally block is left by a jump.
+    DropRaised,
+    /// Pushes the module loaded from the path in the names pool.
+    Import(u32),
+}
+
+#[derive(Default)]
+pub struct Chunk {
+    code: Vec<OpCode>,
+    constants: Vec<Value>,
+    names: Vec<String>,
+    functions: Vec<Rc<Proto>>,
+    /// Run length encoded line table: each entry gives the token of the
+    /// instructions starting at its offset, up to the next entry.
+    lines: Vec<(usize, Token)>,
+}
+
+/// A compiled function, ready to be turned into a closure.
+pub struct Proto {
+    name: String,
+    params: Vec<String>,
+    kind: ProtoKind,
+    chunk: Chunk,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProtoKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: OpCode, token: Option<&Token>) -> usize {
+        if let Some(token) = token {
+            let changed = match self.lines.last() {
+                Some((_, last)) => last.span() != token.span() || last.line() != token.line(),
+                None => true,
+            };
+            if changed {
+                self.lines.push((self.code.len(), token.clone()));
+            }
+        }
+        self.code.push(op);
+        self.code.len() - 1
+    }
+    pub fn patch(&mut self, offset: usize, op: OpCode) {
+        self.code[offset] = op;
+    }
+    pub fn add_constant(&mut self, value: Value) -> u32 {
+        self.constants.push(value);
+        (self.constants.len() - 1) as u32
+    }
+    pub fn add_name(&mut self, name: &str) -> u32 {
+        match self.names.iter().position(|n| n == name) {
+            Some(index) => index as u32,
+            None => {
+                self.names.push(name.to_string());
+                (self.names.len() - 1) as u32
+            }
+        }
+    }
+    pub fn add_function(&mut self, proto: Proto) -> u32 {
+        self.functions.push(Rc::new(proto));
+        (self.functions.len() - 1) as u32
+    }
+    pub fn code(&self) -> &Vec<OpCode> {
+        &self.code
+    }
+    pub fn constant(&self, index: u32) -> &Value {
+        &self.constants[index as usize]
+    }
+    pub fn name(&self, index: u32) -> &String {
+        &self.names[index as usize]
+    }
+    pub fn function(&self, index: u32) -> &Rc<Proto> {
+        &self.functions[index as usize]
+    }
+    /// Token the instruction at `offset` was compiled from.
+    pub fn token(&self, offset: usize) -> Option<&Token> {
+        let index = self.lines.partition_point(|(start, _)| *start <= offset);
+        index.checked_sub(1).map(|i| &self.lines[i].1)
+    }
+}
+
+impl Proto {
+    pub fn new(name: String, params: Vec<String>, kind: ProtoKind, chunk: Chunk) -> Proto {
+        Proto {
+            name,
+            params,
+            kind,
+            chunk,
+        }
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn params(&self) -> &Vec<String> {
+        &self.params
+    }
+    pub fn kind(&self) -> ProtoKind {
+        self.kind
+    }
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+}
@@ -0,0 +1,86 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{function::Function, gc, interpret::Value};
+
+pub type InstanceRef = Rc<RefCell<Instance>>;
+
+pub struct Class {
+    name: String,
+    superclass: Option<Rc<Class>>,
+    methods: HashMap<String, Function>,
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Class {
+    pub fn create(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Function>,
+    ) -> Rc<Class> {
+        let class = Rc::new(Class {
+            name,
+            superclass,
+            methods,
+        });
+        gc::track_class(&class);
+        class
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn superclass(&self) -> Option<Rc<Class>> {
+        self.superclass.clone()
+    }
+    pub fn methods(&self) -> impl Iterator<Item = &Function> {
+        self.methods.values()
+    }
+    pub fn find_method(&self, name: &str) -> Option<Function> {
+        match self.methods.get(name) {
+            Some(m) => Some(m.clone()),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+}
+
+pub struct Instance {
+    class: Rc<Class>,
+    fields: HashMap<String, Value>,
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Instance {
+    pub fn create(class: Rc<Class>) -> InstanceRef {
+        let instance = Rc::new(RefCell::new(Instance {
+            class,
+            fields: HashMap::new(),
+        }));
+        gc::track_instance(&instance);
+        instance
+    }
+    pub fn class(&self) -> Rc<Class> {
+        self.class.clone()
+    }
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.get(name).cloned()
+    }
+    pub fn set_field(&mut self, name: String, value: Value) {
+        self.fields.insert(name, value);
+    }
+    pub fn fields(&self) -> impl Iterator<Item = &Value> {
+        self.fields.values()
+    }
+    /// Removes all fields, used to break reference cycles.
+    pub fn take_fields(&mut self) -> HashMap<String, Value> {
+        std::mem::take(&mut self.fields)
+    }
+}
@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        AssignExpr, Ast, AstNodeKind, AstNodeRef, BinaryExpr, Block, BreakStmt, ClassDecl,
+        CoalesceExpr, ConditionalExpr, ContinueStmt, ExprStmt, ForStmt, FunCall, FunDecl, FunDef,
+        GetExpr, GroupExpr, IfStmt, ImportStmt, IndexExpr, IndexSetExpr, InterpolationExpr,
+        ListExpr, LiteralExpr, MapExpr, PrintStmt, Program, ReturnStmt, SetExpr, SuperExpr,
+        ThrowStmt, TryStmt, UnaryExpr, VarDecl, WhileStmt,
+    },
+    chunk::{Chunk, OpCode, Proto, ProtoKind},
+    interpret::Value,
+    token::{Token, TokenKind},
+};
+
+/// Where a local variable lives at runtime. Variables that closures refer
+/// to are kept in an environment, the others in a stack slot of the frame.
+#[derive(Clone, Copy)]
+enum Storage {
+    Slot(u32),
+    Env,
+}
+
+enum Variable {
+    Local(u32),
+    Env(u16),
+    Global,
+}
+
+struct Scope {
+    names: HashMap<String, Storage>,
+    captured: Vec<String>,
+    /// Whether the scope has an environment of its own at runtime.
+    env: bool,
+    slots: u32,
+}
+
+struct Loop {
+    label: Option<String>,
+    start: usize,
+    scopes: usize,
+    /// Try statements of the function around the loop.
+    tries: usize,
+    breaks: Vec<usize>,
+    /// Jumps to the increment of a for loop, or the condition of a while
+    /// loop.
+    continues: Vec<usize>,
+}
+
+/// A try statement whose handler is active at runtime, or a finally block
+/// run for an error, which keeps the error until it is raised again.
+struct Try {
+    scopes: usize,
+    finally: Option<AstNodeRef>,
+    raised: bool,
+}
+
+struct FunctionState {
+    name: String,
+    params: Vec<String>,
+    kind: ProtoKind,
+    chunk: Chunk,
+    /// Stack slots in use, including the one holding the callee.
+    slots: u32,
+    loops: Vec<Loop>,
+    tries: Vec<Try>,
+}
+
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    /// Mirrors the scopes of the resolver, so resolved depths index it.
+    scopes: Vec<Scope>,
+    token: Option<Token>,
+}
+
+/// Compiles a resolved `ast` into the function run at the top level.
+pub fn compile(ast: &Ast) -> Proto {
+    let mut compiler = Compiler::new();
+    ast.root().compile(&mut compiler);
+    if let AstNodeKind::Program = ast.root().kind() {
+        compiler.emit(OpCode::Nil);
+    }
+    compiler.emit(OpCode::Return);
+    compiler.end_function()
+}
+
+impl Compiler {
+    fn new() -> Compiler {
+        let mut compiler = Compiler {
+            functions: vec![],
+            scopes: vec![],
+            token: None,
+        };
+        compiler.begin_function("script", vec![], ProtoKind::Script);
+        compiler
+    }
+    fn begin_function(&mut self, name: &str, params: Vec<String>, kind: ProtoKind) {
+        self.functions.push(FunctionState {
+            name: name.to_string(),
+            slots: params.len() as u32 + 1,
+            params,
+            kind,
+            chunk: Chunk::default(),
+            loops: vec![],
+            tries: vec![],
+        });
+    }
+    fn end_function(&mut self) -> Proto {
+        let function = self.functions.pop().expect("no function being compiled");
+        Proto::new(
+            function.name,
+            function.params,
+            function.kind,
+            function.chunk,
+        )
+    }
+    fn function(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("no function being compiled")
+    }
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.function().chunk
+    }
+    /// Sets the token reported by runtime errors of the next instructions.
+    fn at(&mut self, token: &Token) {
+        self.token = Some(token.clone());
+    }
+    fn emit(&mut self, op: OpCode) -> usize {
+        let function = self
+            .functions
+            .last_mut()
+            .expect("no function being compiled");
+        function.chunk.write(op, self.token.as_ref())
+    }
+    fn emit_jump(&mut self, op: fn(u32) -> OpCode) -> usize {
+        self.emit(op(0))
+    }
+    /// Points the jump at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) {
+        let target = self.chunk().code().len() as u32;
+        let op = match self.chunk().code()[offset] {
+            OpCode::JumpIfFalse(_) => OpCode::JumpIfFalse(target),
+            OpCode::Try(_, finally) => OpCode::Try(target, finally),
+            _ => OpCode::Jump(target),
+        };
+        self.chunk().patch(offset, op);
+    }
+    fn emit_constant(&mut self, value: Value) {
+        let index = self.chunk().add_constant(value);
+        self.emit(OpCode::Constant(index));
+    }
+    fn name(&mut self, name: &str) -> u32 {
+        self.chunk().add_name(name)
+    }
+    /// Returns whether the scope needs an environment at runtime.
+    fn begin_scope(&mut self, captured: Vec<String>) -> bool {
+        let env = !captured.is_empty();
+        self.scopes.push(Scope {
+            names: HashMap::new(),
+            captured,
+            env,
+            slots: 0,
+        });
+        env
+    }
+    fn end_scope(&mut self) -> Scope {
+        let scope = self.scopes.pop().expect("no scope to end");
+        self.function().slots -= scope.slots;
+        scope
+    }
+    fn emit_scope_exit(&mut self, slots: u32, envs: usize) {
+        match slots {
+            0 => {}
+            1 => {
+                self.emit(OpCode::Pop);
+            }
+            n => {
+                self.emit(OpCode::PopN(n));
+            }
+        }
+        for _ in 0..envs {
+            self.emit(OpCode::PopEnv);
+        }
+    }
+    /// Emits the exit of the open scopes from `from` up to `to`. When
+    /// `value` is set the value on top of the stack is moved below them.
+    fn emit_exit(&mut self, from: usize, to: usize, value: bool) {
+        let exited = &self.scopes[from..to];
+        let slots = exited.iter().map(|s| s.slots).sum();
+        let envs = exited.iter().filter(|s| s.env).count();
+        if value && slots > 0 {
+            let above = self.scopes[from..].iter().map(|s| s.slots).sum::<u32>();
+            let first = self.function().slots - above;
+            self.emit(OpCode::SetLocal(first));
+        }
+        self.emit_scope_exit(slots, envs);
+    }
+    /// Leaves the try statements entered after the first `tries` of the
+    /// function, innermost first, running their finally blocks. `value`
+    /// keeps the value on top of the stack. Returns how many scopes are
+    /// still open afterwards.
+    fn exit_tries(&mut self, tries: usize, value: bool) -> usize {
+        let mut depth = self.scopes.len();
+        let mut exited = vec![];
+        while self.function().tries.len() > tries {
+            let t = self.function().tries.pop().expect("no try to exit");
+            self.emit_exit(t.scopes, depth, value);
+            self.emit(match t.raised {
+                true => OpCode::DropRaised,
+                false => OpCode::EndTry,
+            });
+            if let Some(finally) = &t.finally {
+                self.compile_finally(finally, t.scopes, value);
+            }
+            depth = t.scopes;
+            exited.push(t);
+        }
+        self.function().tries.extend(exited.into_iter().rev());
+        depth
+    }
+    /// Compiles `finally` as if the scopes above the first `scopes` were
+    /// closed. `value` reserves a slot for the value on top of the stack.
+    fn compile_finally(&mut self, finally: &AstNodeRef, scopes: usize, value: bool) {
+        let inner = self.scopes.split_off(scopes);
+        let slots = self.function().slots;
+        let closed = inner.iter().map(|s| s.slots).sum::<u32>();
+        self.function().slots = slots - closed + value as u32;
+        if let (true, Some(scope)) = (value, self.scopes.last_mut()) {
+            scope.slots += 1;
+        }
+        finally.compile(self);
+        if let (true, Some(scope)) = (value, self.scopes.last_mut()) {
+            scope.slots -= 1;
+        }
+        self.function().slots = slots;
+        self.scopes.extend(inner);
+    }
+    /// Declares `name` in the innermost scope, `None` meaning a global.
+    fn declare(&mut self, name: &str) -> Option<Storage> {
+        let next = self.functions.last()?.slots;
+        let scope = self.scopes.last_mut()?;
+        let storage = if scope.captured.iter().any(|c| c == name) {
+            Storage::Env
+        } else {
+            scope.slots += 1;
+            Storage::Slot(next)
+        };
+        scope.names.insert(name.to_string(), storage);
+        if let Storage::Slot(_) = storage {
+            self.function().slots += 1;
+        }
+        Some(storage)
+    }
+    /// Binds the value on top of the stack to a declared name.
+    fn define(&mut self, name: &str, storage: Option<Storage>) {
+        match storage {
+            Some(Storage::Slot(_)) => {}
+            Some(Storage::Env) => {
+                let name = self.name(name);
+                self.emit(OpCode::DefineEnv(name));
+            }
+            None => {
+                let name = self.name(name);
+                self.emit(OpCode::DefineGlobal(name));
+            }
+        }
+    }
+    fn variable(&self, name: &str, depth: Option<usize>) -> Variable {
+        let Some(depth) = depth else {
+            return Variable::Global;
+        };
+        let index = self.scopes.len() - 1 - depth;
+        match self.scopes[index].names.get(name) {
+            Some(Storage::Slot(slot)) => Variable::Local(*slot),
+            Some(Storage::Env) => {
+                Variable::Env(self.scopes[index + 1..].iter().filter(|s| s.env).count() as u16)
+            }
+            None => Variable::Global,
+        }
+    }
+    fn get_variable(&mut self, name: &str, depth: Option<usize>) {
+        let op = match self.variable(name, depth) {
+            Variable::Local(slot) => OpCode::GetLocal(slot),
+            Variable::Env(distance) => OpCode::GetEnv(distance, self.name(name)),
+            Variable::Global => OpCode::GetGlobal(self.name(name)),
+        };
+        self.emit(op);
+    }
+    fn set_variable(&mut self, name: &str, depth: Option<usize>) {
+        let op = match self.variable(name, depth) {
+            Variable::Local(slot) => OpCode::SetLocal(slot),
+            Variable::Env(distance) => OpCode::SetEnv(distance, self.name(name)),
+            Variable::Global => OpCode::SetGlobal(self.name(name)),
+        };
+        self.emit(op);
+    }
+    fn compile_all(&mut self, nodes: &[AstNodeRef]) {
+        for n in nodes {
+            n.compile(self);
+        }
+    }
+    /// Compiles a function body and emits the instruction creating its
+    /// closure.
+    fn compile_function(
+        &mut self,
+        name: &str,
+        params: &[Token],
+        block: &AstNodeRef,
+        captured: Vec<String>,
+        kind: ProtoKind,
+    ) {
+        let names = params.iter().map(|p| p.text().clone()).collect();
+        self.begin_function(name, names, kind);
+        if self.begin_scope(captured) {
+            self.emit(OpCode::PushEnv);
+        }
+        for (i, p) in params.iter().enumerate() {
+            let slot = i as u32 + 1;
+            let scope = self.scopes.last_mut().expect("no function scope");
+            if scope.captured.contains(p.text()) {
+                scope.names.insert(p.text().clone(), Storage::Env);
+                let name = self.name(p.text());
+                self.emit(OpCode::GetLocal(slot));
+                self.emit(OpCode::DefineEnv(name));
+            } else {
+                scope.names.insert(p.text().clone(), Storage::Slot(slot));
+            }
+        }
+        block.compile(self);
+        self.emit(OpCode::Nil);
+        self.emit(OpCode::Return);
+        self.end_scope();
+        let proto = self.end_function();
+        let index = self.chunk().add_function(proto);
+        self.emit(OpCode::Closure(index));
+    }
+    pub fn compile_literal(&mut self, node: &LiteralExpr) {
+        let token = node.token();
+        match token.kind() {
+            TokenKind::Nil => {
+                self.emit(OpCode::Nil);
+            }
+            TokenKind::True => {
+                self.emit(OpCode::True);
+            }
+            TokenKind::False => {
+                self.emit(OpCode::False);
+            }
+            TokenKind::Number => {
+                let num = token
+                    .text()
+                    .parse::<f64>()
+                    .expect("scanner only produces valid numbers");
+                self.emit_constant(Value::Number(num));
+            }
+            TokenKind::String => {
+                let text = token.text()[1..token.text().len() - 1].to_owned();
+                self.emit_constant(Value::String(text));
+            }
+            TokenKind::Identifier | TokenKind::This => {
+                self.at(token);
+                self.get_variable(token.text(), node.depth());
+            }
+            _ => {}
+        }
+    }
+    pub fn compile_group(&mut self, node: &GroupExpr) {
+        node.expr().compile(self);
+    }
+    pub fn compile_assignment(&mut self, node: &AssignExpr) {
+        node.expr().compile(self);
+        self.at(node.variable());
+        self.set_variable(node.variable().text(), node.depth());
+    }
+    pub fn compile_conditional(&mut self, node: &ConditionalExpr) {
+        node.condition().compile(self);
+        let otherwise = self.emit_jump(OpCode::JumpIfFalse);
+        node.then().compile(self);
+        let end = self.emit_jump(OpCode::Jump);
+        self.patch_jump(otherwise);
+        node.otherwise().compile(self);
+        self.patch_jump(end);
+    }
+    pub fn compile_coalesce(&mut self, node: &CoalesceExpr) {
+        node.lexpr().compile(self);
+        self.emit(OpCode::Dup);
+        self.emit(OpCode::Nil);
+        self.emit(OpCode::Equal);
+        // a value other than nil stays on the stack as the result
+        let end = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        node.rexpr().compile(self);
+        self.patch_jump(end);
+    }
+    pub fn compile_unary(&mut self, node: &UnaryExpr) {
+        node.expr().compile(self);
+        self.at(node.token());
+        if node.token().kind() == TokenKind::Bang {
+            self.emit(OpCode::Not);
+        } else {
+            self.emit(OpCode::Negate);
+        }
+    }
+    pub fn compile_binary(&mut self, node: &BinaryExpr) {
+        match node.token().kind() {
+            TokenKind::And => {
+                node.lexpr().compile(self);
+                let short = self.emit_jump(OpCode::JumpIfFalse);
+                node.rexpr().compile(self);
+                let end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(short);
+                self.emit(OpCode::False);
+                self.patch_jump(end);
+            }
+            TokenKind::Or => {
+                node.lexpr().compile(self);
+                let right = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::True);
+                let end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(right);
+                node.rexpr().compile(self);
+                self.patch_jump(end);
+            }
+            kind => {
+                node.lexpr().compile(self);
+                node.rexpr().compile(self);
+                self.at(node.token());
+                self.emit(match kind {
+                    TokenKind::EqualEqual => OpCode::Equal,
+                    TokenKind::BangEqual => OpCode::NotEqual,
+                    TokenKind::Plus => OpCode::Add,
+                    TokenKind::Minus => OpCode::Subtract,
+                    TokenKind::Star => OpCode::Multiply,
+                    TokenKind::Slash => OpCode::Divide,
+                    TokenKind::Greater => OpCode::Greater,
+                    TokenKind::GreaterEqual => OpCode::GreaterEqual,
+                    TokenKind::Less => OpCode::Less,
+                    TokenKind::LessEqual => OpCode::LessEqual,
+                    _ => unreachable!("parser produced binary operator {}", kind),
+                });
+            }
+        }
+    }
+    pub fn compile_if_stmt(&mut self, node: &IfStmt) {
+        node.expr().compile(self);
+        let otherwise = self.emit_jump(OpCode::JumpIfFalse);
+        node.stmt().compile(self);
+        match node.elstmt() {
+            Some(elstmt) => {
+                let end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(otherwise);
+                elstmt.compile(self);
+                self.patch_jump(end);
+            }
+            None => self.patch_jump(otherwise),
+        }
+    }
+    pub fn compile_while_stmt(&mut self, node: &WhileStmt) {
+        let start = self.chunk().code().len();
+        node.expr().compile(self);
+        let exit = self.emit_jump(OpCode::JumpIfFalse);
+        let scopes = self.scopes.len();
+        let tries = self.function().tries.len();
+        self.function().loops.push(Loop {
+            label: node.label().map(|l| l.text().clone()),
+            start,
+            scopes,
+            tries,
+            breaks: vec![],
+            continues: vec![],
+        });
+        node.stmt().compile(self);
+        let lp = self.function().loops.pop().expect("loop ended twice");
+        for c in lp.continues {
+            self.patch_jump(c);
+        }
+        self.emit(OpCode::Jump(lp.start as u32));
+        self.patch_jump(exit);
+        for b in lp.breaks {
+            self.patch_jump(b);
+        }
+    }
+    pub fn compile_for_stmt(&mut self, node: &ForStmt) {
+        if self.begin_scope(node.captured()) {
+            self.emit(OpCode::PushEnv);
+        }
+        if let Some(init) = node.initialize() {
+            init.compile(self);
+        }
+        let start = self.chunk().code().len();
+        let exit = node.condition().map(|condition| {
+            condition.compile(self);
+            self.emit_jump(OpCode::JumpIfFalse)
+        });
+        let scopes = self.scopes.len();
+        let tries = self.function().tries.len();
+        self.function().loops.push(Loop {
+            label: node.label().map(|l| l.text().clone()),
+            start,
+            scopes,
+            tries,
+            breaks: vec![],
+            continues: vec![],
+        });
+        node.stmt().compile(self);
+        let lp = self.function().loops.pop().expect("loop ended twice");
+        for c in lp.continues {
+            self.patch_jump(c);
+        }
+        if let Some(increment) = node.increment() {
+            increment.compile(self);
+            self.emit(OpCode::Pop);
+        }
+        self.at(node.keyword());
+        self.emit(OpCode::Jump(lp.start as u32));
+        if let Some(exit) = exit {
+            self.patch_jump(exit);
+        }
+        for b in lp.breaks {
+            self.patch_jump(b);
+        }
+        let scope = self.end_scope();
+        self.emit_scope_exit(scope.slots, scope.env as usize);
+    }
+    /// Leaves the scopes and try statements inside the loop labeled
+    /// `label`, or the innermost loop. Returns the loop and the jump to
+    /// patch.
+    fn emit_loop_exit(&mut self, token: &Token, label: Option<&Token>) -> Option<(usize, usize)> {
+        let index = self
+            .function()
+            .loops
+            .iter()
+            .rposition(|l| label.is_none_or(|label| l.label.as_ref() == Some(label.text())))?;
+        let lp = &self.function().loops[index];
+        let (scopes, tries) = (lp.scopes, lp.tries);
+        self.at(token);
+        let depth = self.exit_tries(tries, false);
+        self.emit_exit(scopes, depth, false);
+        Some((index, self.emit_jump(OpCode::Jump)))
+    }
+    pub fn compile_break_stmt(&mut self, node: &BreakStmt) {
+        if let Some((index, jump)) = self.emit_loop_exit(node.token(), node.label()) {
+            self.function().loops[index].breaks.push(jump);
+        }
+    }
+    pub fn compile_continue_stmt(&mut self, node: &ContinueStmt) {
+        if let Some((index, jump)) = self.emit_loop_exit(node.token(), node.label()) {
+            self.function().loops[index].continues.push(jump);
+        }
+    }
+    pub fn compile_return_stmt(&mut self, node: &ReturnStmt) {
+        match node.expr() {
+            Some(e) => e.compile(self),
+            None => {
+                self.emit(OpCode::Nil);
+            }
+        }
+        self.at(node.token());
+        self.exit_tries(0, true);
+        self.emit(OpCode::Return);
+    }
+    pub fn compile_throw_stmt(&mut self, node: &ThrowStmt) {
+        node.expr().compile(self);
+        self.at(node.token());
+        self.emit(OpCode::Throw);
+    }
+    pub fn compile_try_stmt(&mut self, node: &TryStmt) {
+        let scopes = self.scopes.len();
+        let finally = node.finally().cloned();
+        self.at(node.token());
+        // The finally handler is installed first, so that it also covers
+        // the catch block.
+        let finally_handler = finally.as_ref().map(|f| {
+            let handler = self.emit(OpCode::Try(0, true));
+            self.function().tries.push(Try {
+                scopes,
+                finally: Some(f.clone()),
+                raised: false,
+            });
+            handler
+        });
+        if let Some((param, block)) = node.catch() {
+            let handler = self.emit(OpCode::Try(0, false));
+            self.function().tries.push(Try {
+                scopes,
+                finally: None,
+                raised: false,
+            });
+            node.body().compile(self);
+            self.function().tries.pop();
+            self.emit(OpCode::EndTry);
+            let done = self.emit_jump(OpCode::Jump);
+            self.patch_jump(handler);
+            if self.begin_scope(node.captured()) {
+                self.emit(OpCode::PushEnv);
+            }
+            // The handler pushed the caught value where the slot is.
+            let storage = self.declare(param.text());
+            self.define(param.text(), storage);
+            block.compile(self);
+            let scope = self.end_scope();
+            self.emit_scope_exit(scope.slots, scope.env as usize);
+            self.patch_jump(done);
+        } else {
+            node.body().compile(self);
+        }
+        if let (Some(finally), Some(handler)) = (finally, finally_handler) {
+            self.function().tries.pop();
+            self.emit(OpCode::EndTry);
+            finally.compile(self);
+            let end = self.emit_jump(OpCode::Jump);
+            self.patch_jump(handler);
+            self.function().tries.push(Try {
+                scopes,
+                finally: None,
+                raised: true,
+            });
+            finally.compile(self);
+            self.function().tries.pop();
+            self.emit(OpCode::Rethrow);
+            self.patch_jump(end);
+        }
+    }
+    pub fn compile_print_stmt(&mut self, node: &PrintStmt) {
+        node.expr().compile(self);
+        self.at(node.token());
+        self.emit(OpCode::Print);
+    }
+    pub fn compile_expr_stmt(&mut self, node: &ExprStmt) {
+        node.expr().compile(self);
+        self.emit(OpCode::Pop);
+    }
+    pub fn compile_var_decl(&mut self, node: &VarDecl) {
+        let storage = self.declare(node.name().text());
+        match node.expr() {
+            Some(e) => e.compile(self),
+            None => {
+                self.emit(OpCode::Nil);
+            }
+        }
+        self.define(node.name().text(), storage);
+    }
+    pub fn compile_import_stmt(&mut self, node: &ImportStmt) {
+        let storage = self.declare(node.name().text());
+        let path = node.path().text();
+        let path = self.name(&path[1..path.len() - 1]);
+        self.at(node.path());
+        self.emit(OpCode::Import(path));
+        self.define(node.name().text(), storage);
+    }
+    pub fn compile_fun_decl(&mut self, node: &FunDecl) {
+        let name = node.name().text();
+        let storage = self.declare(name);
+        self.compile_function(
+            name,
+            node.params(),
+            node.block(),
+            node.captured(),
+            ProtoKind::Function,
+        );
+        self.define(name, storage);
+    }
+    pub fn compile_fun_def(&mut self, node: &FunDef) {
+        self.compile_function(
+            "<anonymous>",
+            node.params(),
+            node.block(),
+            node.captured(),
+            ProtoKind::Function,
+        );
+    }
+    pub fn compile_fun_call(&mut self, node: &FunCall) {
+        node.callee().compile(self);
+        self.compile_all(node.args());
+        self.at(node.paren());
+        self.emit(OpCode::Call(node.args().len() as u32));
+    }
+    pub fn compile_class_decl(&mut self, node: &ClassDecl) {
+        let name = node.name().text();
+        let storage = self.declare(name);
+        if let Some(s) = node.superclass() {
+            s.compile(self);
+            self.at(node.name());
+            self.emit(OpCode::Inherit);
+            self.emit(OpCode::Dup);
+            self.emit(OpCode::PushEnv);
+            self.begin_scope(vec!["super".to_string()]);
+            let storage = self.declare("super");
+            self.define("super", storage);
+        }
+        // The environment holding `this` is created when a method is bound.
+        self.begin_scope(vec!["this".to_string()]);
+        self.declare("this");
+        for m in node.methods() {
+            let kind = if m.name().text() == "init" {
+                ProtoKind::Initializer
+            } else {
+                ProtoKind::Method
+            };
+            self.compile_function(m.name().text(), m.params(), m.block(), m.captured(), kind);
+        }
+        self.end_scope();
+        self.at(node.name());
+        let index = self.name(name);
+        let count = node.methods().len() as u16;
+        self.emit(OpCode::Class(index, count, node.superclass().is_some()));
+        if node.superclass().is_some() {
+            self.emit(OpCode::PopEnv);
+            self.end_scope();
+        }
+        self.define(name, storage);
+    }
+    pub fn compile_get_expr(&mut self, node: &GetExpr) {
+        node.object().compile(self);
+        self.at(node.name());
+        let name = self.name(node.name().text());
+        self.emit(OpCode::GetProperty(name));
+    }
+    pub fn compile_set_expr(&mut self, node: &SetExpr) {
+        node.object().compile(self);
+        self.at(node.name());
+        self.emit(OpCode::ExpectInstance);
+        node.expr().compile(self);
+        self.at(node.name());
+        let name = self.name(node.name().text());
+        self.emit(OpCode::SetProperty(name));
+    }
+    pub fn compile_super_expr(&mut self, node: &SuperExpr) {
+        let depth = node.depth().expect("'super' is resolved before compiling");
+        self.at(node.keyword());
+        self.get_variable("this", Some(depth - 1));
+        self.get_variable("super", Some(depth));
+        self.at(node.method());
+        let name = self.name(node.method().text());
+        self.emit(OpCode::GetSuper(name));
+    }
+    pub fn compile_list_expr(&mut self, node: &ListExpr) {
+        self.compile_all(node.items());
+        self.at(node.bracket());
+        self.emit(OpCode::List(node.items().len() as u32));
+    }
+    pub fn compile_interpolation_expr(&mut self, node: &InterpolationExpr) {
+        self.emit_constant(Value::String(node.strings()[0].clone()));
+        for (expr, string) in node.exprs().iter().zip(&node.strings()[1..]) {
+            expr.compile(self);
+            self.emit_constant(Value::String(string.clone()));
+        }
+        self.at(node.token());
+        self.emit(OpCode::Concat(node.strings().len() as u32 * 2 - 1));
+    }
+    pub fn compile_map_expr(&mut self, node: &MapExpr) {
+        for (key, value) in node.entries() {
+            key.compile(self);
+            value.compile(self);
+        }
+        self.at(node.brace());
+        self.emit(OpCode::Map(node.entries().len() as u32));
+    }
+    pub fn compile_index_expr(&mut self, node: &IndexExpr) {
+        node.object().compile(self);
+        node.index().compile(self);
+        self.at(node.bracket());
+        self.emit(OpCode::GetIndex);
+    }
+    pub fn compile_index_set_expr(&mut self, node: &IndexSetExpr) {
+        node.object().compile(self);
+        node.index().compile(self);
+        node.expr().compile(self);
+        self.at(node.bracket());
+        self.emit(OpCode::SetIndex);
+    }
+    pub fn compile_program(&mut self, node: &Program) {
+        self.compile_all(node.decs());
+    }
+    pub fn compile_block(&mut self, node: &Block) {
+        if self.begin_scope(node.captured()) {
+            self.emit(OpCode::PushEnv);
+        }
+        self.compile_all(node.decs());
+        let scope = self.end_scope();
+        self.emit_scope_exit(scope.slots, scope.env as usize);
+    }
+}
@@ -1,8 +1,8 @@
-use crate::interpret::Value;
+use crate::{gc, interpret::Value};
 use std::cell::RefCell;
 use std::collections::hash_map::HashMap;
-use std::sync::Arc;
-pub type Env = Arc<RefCell<Environment>>;
+use std::rc::Rc;
+pub type Env = Rc<RefCell<Environment>>;
 
 #[derive(Default)]
 pub struct Environment {
@@ -12,9 +12,12 @@ pub struct Environment {
 
 impl Environment {
     pub fn new(par: Option<Env>) -> Env {
-        let mut env = Environment::default();
-        env.parent = par;
-        Arc::new(RefCell::new(env))
+        let env = Rc::new(RefCell::new(Environment {
+            parent: par,
+            ..Default::default()
+        }));
+        gc::track_env(&env);
+        env
     }
     pub fn get(&self, name: &String) -> Option<Value> {
         if let Some(v) = self.map.get(name) {
@@ -35,11 +38,71 @@ impl Environment {
             false
         }
     }
+    pub fn get_at(env: &Env, distance: usize, name: &String) -> Option<Value> {
+        Self::ancestor(env, distance)
+            .borrow()
+            .map
+            .get(name)
+            .cloned()
+    }
+    pub fn assign_at(env: &Env, distance: usize, name: String, value: Value) -> bool {
+        match Self::ancestor(env, distance)
+            .borrow_mut()
+            .map
+            .get_mut(&name)
+        {
+            Some(v) => {
+                *v = value;
+                true
+            }
+            None => false,
+        }
+    }
+    fn ancestor(env: &Env, distance: usize) -> Env {
+        let mut env = env.clone();
+        for _ in 0..distance {
+            let parent = env.borrow().parent().expect("resolved scope out of range");
+            env = parent;
+        }
+        env
+    }
+    /// The global environment at the end of the chain starting at `env`,
+    /// the one of the file the code comes from.
+    pub fn root(env: &Env) -> Env {
+        let mut env = env.clone();
+        loop {
+            let parent = env.borrow().parent();
+            match parent {
+                Some(parent) => env = parent,
+                None => return env,
+            }
+        }
+    }
     pub fn init(&mut self, name: String, value: Value) {
         self.map.insert(name, value);
     }
 
-    pub fn parent(&self) -> Option<Arc<RefCell<Environment>>> {
+    /// Bindings declared directly in this environment, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut all = self
+            .map
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<Vec<(String, Value)>>();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.map.values()
+    }
+
+    /// Removes all bindings and the parent, used to break reference cycles.
+    pub fn take(&mut self) -> (HashMap<String, Value>, Option<Env>) {
+        (std::mem::take(&mut self.map), self.parent.take())
+    }
+
+    pub fn parent(&self) -> Option<Rc<RefCell<Environment>>> {
         self.parent.clone()
     }
 }
@@ -0,0 +1,220 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    interpret::Value,
+    token::{Token, TokenKind},
+};
+
+/// Range of character offsets into the source text, `end` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Deepest nesting of calls before either backend gives up.
+pub const MAX_FRAMES: usize = 4096;
+
+/// A function call that was active when a runtime error happened, or a
+/// module the error escaped from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Function name, or file of a module.
+    pub function: String,
+    /// One based line of the call site, 0 for a call made by the host. For
+    /// a module, the line in its file.
+    pub line: usize,
+    pub module: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorInfo {
+    line: usize,
+    column: usize,
+    message: String,
+    span: Span,
+    trace: Vec<Frame>,
+    /// The value of a `throw` statement, runtime errors raised by the
+    /// interpreter itself have none.
+    thrown: Option<Box<Value>>,
+    /// Module the line and column refer to, `None` for the script being
+    /// run.
+    file: Option<Box<Path>>,
+    /// Whether `file` was set, by the innermost call the error escaped.
+    placed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxError {
+    Scan(ErrorInfo),
+    Parse(ErrorInfo),
+    Resolve(ErrorInfo),
+    Runtime(ErrorInfo),
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+impl Frame {
+    pub fn new(function: String, line: usize) -> Frame {
+        Frame {
+            function,
+            line,
+            module: false,
+        }
+    }
+    pub fn module(file: String, line: usize) -> Frame {
+        Frame {
+            function: file,
+            line,
+            module: true,
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.module, self.line) {
+            (true, line) => write!(f, "in module '{}' at line {}", self.function, line),
+            (false, 0) => write!(f, "in {} called by the host", self.function),
+            (false, line) => write!(f, "in {} called at line {}", self.function, line),
+        }
+    }
+}
+
+impl ErrorInfo {
+    /// `line` and `column` are zero based, as stored in tokens.
+    fn new(line: usize, column: usize, span: Span, message: String) -> ErrorInfo {
+        ErrorInfo {
+            line: line + 1,
+            column: column + 1,
+            message,
+            span,
+            trace: vec![],
+            thrown: None,
+            file: None,
+            placed: false,
+        }
+    }
+    fn at(token: &Token, message: String) -> ErrorInfo {
+        let mut info = ErrorInfo::new(token.line(), token.column(), token.span(), message);
+        if token.kind() == TokenKind::Host {
+            info.line = 0;
+            info.column = 0;
+        }
+        info
+    }
+}
+
+impl LoxError {
+    pub fn scan(line: usize, column: usize, span: Span, message: &str) -> LoxError {
+        LoxError::Scan(ErrorInfo::new(line, column, span, message.to_string()))
+    }
+    pub fn parse(token: &Token, message: &str) -> LoxError {
+        LoxError::Parse(ErrorInfo::at(token, message.to_string()))
+    }
+    pub fn resolve(token: &Token, message: &str) -> LoxError {
+        LoxError::Resolve(ErrorInfo::at(token, message.to_string()))
+    }
+    pub fn runtime(token: &Token, message: &str) -> LoxError {
+        LoxError::Runtime(ErrorInfo::at(token, message.to_string()))
+    }
+    pub fn thrown(token: &Token, message: &str, value: Value) -> LoxError {
+        let mut info = ErrorInfo::at(token, message.to_string());
+        info.thrown = Some(Box::new(value));
+        LoxError::Runtime(info)
+    }
+    /// Attaches the call stack, innermost frame first, unless the error
+    /// already carries one.
+    pub fn with_trace(mut self, trace: Vec<Frame>) -> LoxError {
+        if let LoxError::Runtime(info) = &mut self {
+            if info.trace.is_empty() {
+                info.trace = trace;
+            }
+        }
+        self
+    }
+    /// Records the module the error's position is in, unless a call it
+    /// escaped from already did.
+    pub fn placed_in(mut self, file: Option<PathBuf>) -> LoxError {
+        if let LoxError::Runtime(info) = &mut self {
+            if !info.placed {
+                info.file = file.map(PathBuf::into_boxed_path);
+                info.placed = true;
+            }
+        }
+        self
+    }
+    /// The error raised again at `token`, keeping its message and thrown
+    /// value, with `frame` added to the end of its trace.
+    pub fn raised_at(&self, token: &Token, frame: Frame) -> LoxError {
+        let mut info = ErrorInfo::at(token, self.message().clone());
+        info.trace = self.trace().clone();
+        info.trace.push(frame);
+        info.thrown = self.info().thrown.clone();
+        LoxError::Runtime(info)
+    }
+    /// Calls active when a runtime error happened, innermost first.
+    pub fn trace(&self) -> &Vec<Frame> {
+        &self.info().trace
+    }
+    fn info(&self) -> &ErrorInfo {
+        match self {
+            LoxError::Scan(i)
+            | LoxError::Parse(i)
+            | LoxError::Resolve(i)
+            | LoxError::Runtime(i) => i,
+        }
+    }
+    /// One based line number, 0 for errors of a call made by the host,
+    /// which are not in the source.
+    pub fn line(&self) -> usize {
+        self.info().line
+    }
+    /// One based column number, 0 when the line is.
+    pub fn column(&self) -> usize {
+        self.info().column
+    }
+    /// The module `line` and `column` refer to, `None` when they are in
+    /// the script that was run.
+    pub fn file(&self) -> Option<&Path> {
+        self.info().file.as_deref()
+    }
+    pub fn message(&self) -> &String {
+        &self.info().message
+    }
+    pub fn span(&self) -> Span {
+        self.info().span
+    }
+    pub fn thrown_value(&self) -> Option<&Value> {
+        self.info().thrown.as_deref()
+    }
+    pub fn is_runtime(&self) -> bool {
+        matches!(self, LoxError::Runtime(_))
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stage = match self {
+            LoxError::Scan(_) => "Scan",
+            LoxError::Parse(_) => "Parse",
+            LoxError::Resolve(_) => "Resolve",
+            LoxError::Runtime(_) => "Runtime",
+        };
+        if self.line() == 0 {
+            return write!(f, "{} error: {}", stage, self.message());
+        }
+        write!(f, "{} error [line {}:{}", stage, self.line(), self.column())?;
+        if let Some(file) = self.file() {
+            write!(f, " of {}", file.display())?;
+        }
+        write!(f, "]: {}", self.message())
+    }
+}
@@ -0,0 +1,61 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    class::{Class, Instance},
+    environment::Environment,
+    error::LoxError,
+    function::{Arity, Function, Implementation, Runtime},
+    interpret::Value,
+    token::Token,
+};
+
+/// The global `Error` class. Runtime errors of the interpreter reach
+/// `catch` clauses as its instances, with `message` and `line` fields.
+/// Scripts create their own with `Error(message)`.
+pub fn error_class() -> Rc<Class> {
+    let init = Function::create_method(
+        "init".to_string(),
+        Implementation::NativeImpl(Rc::new(error_init), Arity::Fixed(1)),
+        vec!["message".to_string()],
+        Environment::new(None),
+        true,
+    );
+    let methods = HashMap::from([("init".to_string(), init)]);
+    Class::create("Error".to_string(), None, methods)
+}
+
+fn error_init(_: &mut dyn Runtime, args: Vec<Value>) -> Result<Value, LoxError> {
+    let [this, message] = <[Value; 2]>::try_from(args).expect("init is bound to an instance");
+    if let Value::Instance(instance) = &this {
+        instance
+            .borrow_mut()
+            .set_field("message".to_string(), message);
+    }
+    Ok(this)
+}
+
+/// The value a `catch` clause binds for `err`.
+pub fn caught_value(err: &LoxError, class: &Rc<Class>) -> Value {
+    if let Some(value) = err.thrown_value() {
+        return value.clone();
+    }
+    let instance = Instance::create(class.clone());
+    instance
+        .borrow_mut()
+        .set_field("message".to_string(), Value::String(err.message().clone()));
+    instance
+        .borrow_mut()
+        .set_field("line".to_string(), Value::Number(err.line() as f64));
+    Value::Instance(instance)
+}
+
+/// The error raised by throwing `value`. Instances with a `message` field,
+/// caught errors among them, report that message when left uncaught.
+pub fn throw_error(token: &Token, value: Value) -> LoxError {
+    let message = match &value {
+        Value::Instance(i) => i.borrow().field("message"),
+        _ => None,
+    };
+    let message = message.unwrap_or_else(|| value.clone()).to_string();
+    LoxError::thrown(token, &message, value)
+}
@@ -1,22 +1,50 @@
 use std::{
-    sync::Arc,
+    rc::Rc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::{ast::AstNodeRef, environment::Env, interpret::Value};
-type NativeImpl = fn(Vec<Value>) -> Result<Value, ()>;
+use crate::{
+    ast::AstNodeRef,
+    chunk::Proto,
+    environment::{Env, Environment},
+    error::LoxError,
+    interpret::Value,
+    list::{position, List, ListRef},
+    map::MapRef,
+};
+/// What a native function can do with the interpreter running it.
+pub trait Runtime {
+    /// Calls a Lox function or class.
+    fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, LoxError>;
+    /// An error located at the call of the native function.
+    fn error(&self, message: &str) -> LoxError;
+    /// Writes a line of script output.
+    fn print(&mut self, text: &str) -> Result<(), LoxError>;
+}
+
+pub type NativeImpl = Rc<dyn Fn(&mut dyn Runtime, Vec<Value>) -> Result<Value, LoxError>>;
+
+/// Number of arguments a function takes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Arity {
+    Fixed(usize),
+    AtLeast(usize),
+}
 
 #[derive(Clone)]
 pub enum Implementation {
-    NativeImpl(NativeImpl),
+    NativeImpl(NativeImpl, Arity),
     LoxImpl(AstNodeRef),
+    /// Bytecode run by the virtual machine.
+    Compiled(Rc<Proto>),
 }
 
 impl PartialEq for Implementation {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Self::NativeImpl(l0), Self::NativeImpl(r0)) => l0 == r0,
-            (Self::LoxImpl(l0), Self::LoxImpl(r0)) => Arc::ptr_eq(l0, r0),
+            (Self::NativeImpl(l0, _), Self::NativeImpl(r0, _)) => Rc::ptr_eq(l0, r0),
+            (Self::LoxImpl(l0), Self::LoxImpl(r0)) => Rc::ptr_eq(l0, r0),
+            (Self::Compiled(l0), Self::Compiled(r0)) => Rc::ptr_eq(l0, r0),
             _ => false,
         }
     }
@@ -24,9 +52,11 @@ impl PartialEq for Implementation {
 
 #[derive(Clone)]
 pub struct Function {
+    name: String,
     params: Vec<String>,
     code: Implementation,
     closure: Option<Env>,
+    initializer: bool,
 }
 
 impl PartialEq for Function {
@@ -35,34 +65,103 @@ impl PartialEq for Function {
     }
 }
 
+impl Arity {
+    /// Returns the expected number of arguments when `count` doesn't fit.
+    pub fn check(self, count: usize) -> Option<usize> {
+        match self {
+            Arity::Fixed(n) if count != n => Some(n),
+            Arity::AtLeast(n) if count < n => Some(n),
+            _ => None,
+        }
+    }
+}
+
 impl Function {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
     pub fn code(&self) -> &Implementation {
         &self.code
     }
     pub fn params(&self) -> &Vec<String> {
         &self.params
     }
+    pub fn arity(&self) -> Arity {
+        match &self.code {
+            Implementation::NativeImpl(_, arity) => *arity,
+            _ => Arity::Fixed(self.params.len()),
+        }
+    }
     pub fn closure(&self) -> Option<Env> {
         self.closure.clone()
     }
-    pub fn create(code: Implementation, params: Vec<String>, closure: Option<Env>) -> Function {
+    pub fn is_initializer(&self) -> bool {
+        self.initializer
+    }
+    pub fn create(
+        name: String,
+        code: Implementation,
+        params: Vec<String>,
+        closure: Option<Env>,
+    ) -> Function {
         Function {
+            name,
             code,
             params,
             closure,
+            initializer: false,
+        }
+    }
+    pub fn native(name: String, arity: Arity, code: NativeImpl) -> Function {
+        Function::create(name, Implementation::NativeImpl(code, arity), vec![], None)
+    }
+    pub fn create_method(
+        name: String,
+        code: Implementation,
+        params: Vec<String>,
+        closure: Env,
+        initializer: bool,
+    ) -> Function {
+        Function {
+            name,
+            code,
+            params,
+            closure: Some(closure),
+            initializer,
+        }
+    }
+    /// Native methods get the instance as their first argument, see
+    /// [`Function::receiver`].
+    pub fn bind(&self, instance: Value) -> Function {
+        let env = Environment::new(self.closure());
+        env.borrow_mut().init("this".to_string(), instance);
+        Function {
+            name: self.name.clone(),
+            code: self.code.clone(),
+            params: self.params.clone(),
+            closure: Some(env),
+            initializer: self.initializer,
+        }
+    }
+    /// The instance a native method is bound to. It stays in the closure,
+    /// where the collector sees it, rather than in the native code.
+    pub fn receiver(&self) -> Option<Value> {
+        match &self.code {
+            Implementation::NativeImpl(..) => {
+                self.closure.as_ref()?.borrow().get(&"this".to_string())
+            }
+            _ => None,
         }
     }
 }
 
-fn log(args: Vec<Value>) -> Result<Value, ()> {
-    for a in args {
-        print!("{} ", a);
-    }
-    println!();
+fn log(runtime: &mut dyn Runtime, args: Vec<Value>) -> Result<Value, LoxError> {
+    let text = args.iter().map(|a| format!("{} ", a)).collect::<String>();
+    runtime.print(&text)?;
     Ok(Value::Nil)
 }
 
-fn clock(_: Vec<Value>) -> Result<Value, ()> {
+fn clock(_: Vec<Value>) -> Result<Value, String> {
     Ok(Value::Number(
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -71,17 +170,128 @@ fn clock(_: Vec<Value>) -> Result<Value, ()> {
     ))
 }
 
+fn list_arg(function: &str, value: &Value) -> Result<ListRef, String> {
+    match value {
+        Value::List(list) => Ok(list.clone()),
+        _ => Err(format!("{} expects a list", function)),
+    }
+}
+
+fn map_arg(function: &str, value: &Value) -> Result<MapRef, String> {
+    match value {
+        Value::Map(map) => Ok(map.clone()),
+        _ => Err(format!("{} expects a map", function)),
+    }
+}
+
+fn len(args: Vec<Value>) -> Result<Value, String> {
+    match &args[0] {
+        Value::List(list) => Ok(Value::Number(list.borrow().items().len() as f64)),
+        Value::Map(map) => Ok(Value::Number(map.borrow().entries().len() as f64)),
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        _ => Err("len expects a list, a map or a string".to_string()),
+    }
+}
+
+fn push(args: Vec<Value>) -> Result<Value, String> {
+    let list = list_arg("push", &args[0])?;
+    list.borrow_mut().items_mut().push(args[1].clone());
+    Ok(Value::Nil)
+}
+
+fn pop(args: Vec<Value>) -> Result<Value, String> {
+    let list = list_arg("pop", &args[0])?;
+    let item = list.borrow_mut().items_mut().pop();
+    item.ok_or_else(|| "pop from an empty list".to_string())
+}
+
+fn insert(args: Vec<Value>) -> Result<Value, String> {
+    let list = list_arg("insert", &args[0])?;
+    let mut list = list.borrow_mut();
+    let i = position(&args[1], list.items().len() + 1)?;
+    list.items_mut().insert(i, args[2].clone());
+    Ok(Value::Nil)
+}
+
+fn remove(args: Vec<Value>) -> Result<Value, String> {
+    let list = list_arg("remove", &args[0])?;
+    let mut list = list.borrow_mut();
+    let i = position(&args[1], list.items().len())?;
+    Ok(list.items_mut().remove(i))
+}
+
+/// Copies the items from `start` up to, not including, `end`.
+fn slice(args: Vec<Value>) -> Result<Value, String> {
+    let list = list_arg("slice", &args[0])?;
+    let list = list.borrow();
+    let start = position(&args[1], list.items().len() + 1)?;
+    let end = position(&args[2], list.items().len() + 1)?;
+    if start > end {
+        return Err("slice start is after its end".to_string());
+    }
+    Ok(Value::List(List::create(list.items()[start..end].to_vec())))
+}
+
+/// The keys of a map as a list, in the order the map displays them.
+fn keys(args: Vec<Value>) -> Result<Value, String> {
+    let map = map_arg("keys", &args[0])?;
+    let keys = map
+        .borrow()
+        .entries()
+        .keys()
+        .map(|k| k.to_value())
+        .collect();
+    Ok(Value::List(List::create(keys)))
+}
+
+fn values(args: Vec<Value>) -> Result<Value, String> {
+    let map = map_arg("values", &args[0])?;
+    let values = map.borrow().entries().values().cloned().collect();
+    Ok(Value::List(List::create(values)))
+}
+
+fn has(args: Vec<Value>) -> Result<Value, String> {
+    let map = map_arg("has", &args[0])?;
+    let found = map.borrow().has(&args[1])?;
+    Ok(Value::Boolean(found))
+}
+
+fn delete(args: Vec<Value>) -> Result<Value, String> {
+    let map = map_arg("delete", &args[0])?;
+    let removed = map.borrow_mut().delete(&args[1]);
+    removed
+}
+
+/// Builtins report failures as a message, located at the call.
+fn native(
+    name: &str,
+    arity: Arity,
+    code: fn(Vec<Value>) -> Result<Value, String>,
+) -> (String, Function) {
+    let code: NativeImpl =
+        Rc::new(move |runtime, args| code(args).map_err(|message| runtime.error(&message)));
+    (
+        name.to_string(),
+        Function::native(name.to_string(), arity, code),
+    )
+}
+
 pub fn all_natives() -> Vec<(String, Function)> {
-    let mut all = vec![];
-
-    all.push((
-        "log".to_string(),
-        Function::create(Implementation::NativeImpl(log), vec!["".to_string()], None),
-    ));
-    all.push((
-        "clock".to_string(),
-        Function::create(Implementation::NativeImpl(clock), vec![], None),
-    ));
-
-    all
+    vec![
+        (
+            "log".to_string(),
+            Function::native("log".to_string(), Arity::AtLeast(0), Rc::new(log)),
+        ),
+        native("clock", Arity::Fixed(0), clock),
+        native("len", Arity::Fixed(1), len),
+        native("push", Arity::Fixed(2), push),
+        native("pop", Arity::Fixed(1), pop),
+        native("insert", Arity::Fixed(3), insert),
+        native("remove", Arity::Fixed(2), remove),
+        native("slice", Arity::Fixed(3), slice),
+        native("keys", Arity::Fixed(1), keys),
+        native("values", Arity::Fixed(1), values),
+        native("has", Arity::Fixed(2), has),
+        native("delete", Arity::Fixed(2), delete),
+    ]
 }
@@ -0,0 +1,342 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    class::{Class, Instance, InstanceRef},
+    environment::{Env, Environment},
+    interpret::Value,
+    list::{List, ListRef},
+    map::{Map, MapRef},
+    module::{Module, ModuleRef},
+};
+
+/// Allocations before the first automatic collection.
+const INITIAL_THRESHOLD: usize = 10_000;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GcStats {
+    pub collections: usize,
+    /// Objects freed by all collections so far.
+    pub freed: usize,
+    /// Objects alive after the last collection.
+    pub live: usize,
+    /// Objects allocated since the last collection.
+    pub allocated: usize,
+}
+
+/// Weak handles to every environment, instance, class, list, map and
+/// module, the heap objects that can end up in reference cycles.
+struct Heap {
+    envs: Vec<Weak<RefCell<Environment>>>,
+    instances: Vec<Weak<RefCell<Instance>>>,
+    classes: Vec<Weak<Class>>,
+    lists: Vec<Weak<RefCell<List>>>,
+    maps: Vec<Weak<RefCell<Map>>>,
+    modules: Vec<Weak<Module>>,
+    threshold: usize,
+    stats: GcStats,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap {
+        envs: vec![],
+        instances: vec![],
+        classes: vec![],
+        lists: vec![],
+        maps: vec![],
+        modules: vec![],
+        threshold: INITIAL_THRESHOLD,
+        stats: GcStats::default(),
+    });
+}
+
+/// A tracked object, kept alive for the duration of a collection.
+enum Object {
+    Env(Env),
+    Instance(InstanceRef),
+    Class(Rc<Class>),
+    List(ListRef),
+    Map(MapRef),
+    Module(ModuleRef),
+}
+
+pub fn track_env(env: &Env) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.envs.push(Rc::downgrade(env));
+        heap.stats.allocated += 1;
+    });
+}
+
+pub fn track_instance(instance: &InstanceRef) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.instances.push(Rc::downgrade(instance));
+        heap.stats.allocated += 1;
+    });
+}
+
+pub fn track_class(class: &Rc<Class>) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.classes.push(Rc::downgrade(class));
+        heap.stats.allocated += 1;
+    });
+}
+
+pub fn track_list(list: &ListRef) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.lists.push(Rc::downgrade(list));
+        heap.stats.allocated += 1;
+    });
+}
+
+pub fn track_map(map: &MapRef) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.maps.push(Rc::downgrade(map));
+        heap.stats.allocated += 1;
+    });
+}
+
+pub fn track_module(module: &ModuleRef) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.modules.push(Rc::downgrade(module));
+        heap.stats.allocated += 1;
+    });
+}
+
+pub fn stats() -> GcStats {
+    HEAP.with(|heap| heap.borrow().stats)
+}
+
+/// Collects when enough objects were allocated since the last collection.
+pub fn maybe_collect() {
+    if HEAP.with(|heap| {
+        let heap = heap.borrow();
+        heap.stats.allocated >= heap.threshold
+    }) {
+        collect();
+    }
+}
+
+/// Frees the objects only kept alive by reference cycles and returns how
+/// many there were.
+///
+/// Every reference an object receives from another tracked object is
+/// subtracted from its strong count; whatever is left comes from outside
+/// the heap, from the interpreter or the host. Objects reachable from those
+/// are alive, the others are emptied so that their cycles fall apart.
+pub fn collect() -> usize {
+    let objects = HEAP.with(|heap| heap.borrow_mut().upgrade());
+    let index = objects
+        .iter()
+        .enumerate()
+        .map(|(i, o)| (o.address(), i))
+        .collect::<HashMap<usize, usize>>();
+    // One reference to each object is the one held by `objects`.
+    let mut external = objects
+        .iter()
+        .map(|o| o.strong_count() - 1)
+        .collect::<Vec<usize>>();
+    let children = objects
+        .iter()
+        .map(|o| o.children())
+        .collect::<Vec<Option<Vec<usize>>>>();
+    for child in children.iter().flatten().flatten() {
+        if let Some(&i) = index.get(child) {
+            external[i] = external[i].saturating_sub(1);
+        }
+    }
+
+    let mut alive = vec![false; objects.len()];
+    // Objects borrowed right now can't be inspected, so they are kept.
+    let mut pending = (0..objects.len())
+        .filter(|&i| external[i] > 0 || children[i].is_none())
+        .collect::<Vec<usize>>();
+    while let Some(i) = pending.pop() {
+        if alive[i] {
+            continue;
+        }
+        alive[i] = true;
+        for child in children[i].iter().flatten() {
+            if let Some(&c) = index.get(child) {
+                pending.push(c);
+            }
+        }
+    }
+
+    let mut freed = 0;
+    for (o, alive) in objects.iter().zip(alive) {
+        if !alive {
+            o.clear();
+            freed += 1;
+        }
+    }
+    let live = objects.len() - freed;
+    drop(objects);
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.stats.collections += 1;
+        heap.stats.freed += freed;
+        heap.stats.live = live;
+        heap.stats.allocated = 0;
+        heap.threshold = INITIAL_THRESHOLD.max(live * 2);
+    });
+    freed
+}
+
+impl Heap {
+    /// Drops the handles of freed objects and returns the others.
+    fn upgrade(&mut self) -> Vec<Object> {
+        self.envs.retain(|e| e.strong_count() > 0);
+        self.instances.retain(|i| i.strong_count() > 0);
+        self.classes.retain(|c| c.strong_count() > 0);
+        self.lists.retain(|l| l.strong_count() > 0);
+        self.maps.retain(|m| m.strong_count() > 0);
+        self.modules.retain(|m| m.strong_count() > 0);
+        let envs = self
+            .envs
+            .iter()
+            .filter_map(|e| e.upgrade().map(Object::Env));
+        let instances = self
+            .instances
+            .iter()
+            .filter_map(|i| i.upgrade().map(Object::Instance));
+        let classes = self
+            .classes
+            .iter()
+            .filter_map(|c| c.upgrade().map(Object::Class));
+        let lists = self
+            .lists
+            .iter()
+            .filter_map(|l| l.upgrade().map(Object::List));
+        let modules = self
+            .modules
+            .iter()
+            .filter_map(|m| m.upgrade().map(Object::Module));
+        let maps = self
+            .maps
+            .iter()
+            .filter_map(|m| m.upgrade().map(Object::Map));
+        envs.chain(instances)
+            .chain(classes)
+            .chain(lists)
+            .chain(maps)
+            .chain(modules)
+            .collect()
+    }
+}
+
+impl Object {
+    fn address(&self) -> usize {
+        match self {
+            Object::Env(e) => Rc::as_ptr(e) as *const u8 as usize,
+            Object::Instance(i) => Rc::as_ptr(i) as *const u8 as usize,
+            Object::Class(c) => Rc::as_ptr(c) as *const u8 as usize,
+            Object::List(l) => Rc::as_ptr(l) as *const u8 as usize,
+            Object::Map(m) => Rc::as_ptr(m) as *const u8 as usize,
+            Object::Module(m) => Rc::as_ptr(m) as *const u8 as usize,
+        }
+    }
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::Env(e) => Rc::strong_count(e),
+            Object::Instance(i) => Rc::strong_count(i),
+            Object::Class(c) => Rc::strong_count(c),
+            Object::List(l) => Rc::strong_count(l),
+            Object::Map(m) => Rc::strong_count(m),
+            Object::Module(m) => Rc::strong_count(m),
+        }
+    }
+    /// Addresses of the tracked objects this one refers to, one per
+    /// reference, or `None` when it is mutably borrowed.
+    fn children(&self) -> Option<Vec<usize>> {
+        let mut children = vec![];
+        match self {
+            Object::Env(e) => {
+                let env = e.try_borrow().ok()?;
+                if let Some(parent) = env.parent() {
+                    children.push(Object::Env(parent).address());
+                }
+                for v in env.values() {
+                    value_children(v, &mut children);
+                }
+            }
+            Object::Instance(i) => {
+                let instance = i.try_borrow().ok()?;
+                children.push(Object::Class(instance.class()).address());
+                for v in instance.fields() {
+                    value_children(v, &mut children);
+                }
+            }
+            Object::Class(c) => {
+                if let Some(superclass) = c.superclass() {
+                    children.push(Object::Class(superclass).address());
+                }
+                for m in c.methods() {
+                    if let Some(closure) = m.closure() {
+                        children.push(Object::Env(closure).address());
+                    }
+                }
+            }
+            Object::List(l) => {
+                let list = l.try_borrow().ok()?;
+                for v in list.items() {
+                    value_children(v, &mut children);
+                }
+            }
+            Object::Map(m) => {
+                let map = m.try_borrow().ok()?;
+                for v in map.entries().values() {
+                    value_children(v, &mut children);
+                }
+            }
+            Object::Module(m) => children.push(Object::Env(m.env()).address()),
+        }
+        Some(children)
+    }
+    /// Drops everything the object refers to that can be part of a cycle.
+    fn clear(&self) {
+        match self {
+            Object::Env(e) => {
+                let contents = e.borrow_mut().take();
+                drop(contents);
+            }
+            Object::Instance(i) => {
+                let fields = i.borrow_mut().take_fields();
+                drop(fields);
+            }
+            Object::List(l) => {
+                let items = l.borrow_mut().take_items();
+                drop(items);
+            }
+            Object::Map(m) => {
+                let entries = m.borrow_mut().take_entries();
+                drop(entries);
+            }
+            Object::Class(_) | Object::Module(_) => {}
+        }
+    }
+}
+
+fn value_children(value: &Value, children: &mut Vec<usize>) {
+    match value {
+        Value::Function(f) => {
+            if let Some(closure) = f.closure() {
+                children.push(Object::Env(closure).address());
+            }
+        }
+        Value::Class(c) => children.push(Object::Class(c.clone()).address()),
+        Value::Instance(i) => children.push(Object::Instance(i.clone()).address()),
+        Value::List(l) => children.push(Object::List(l.clone()).address()),
+        Value::Map(m) => children.push(Object::Map(m.clone()).address()),
+        Value::Module(m) => children.push(Object::Module(m.clone()).address()),
+        _ => {}
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+};
+
+use crate::{set_stack_limit, Lox, LoxError, STACK_SIZE};
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+const EXPECT_ERROR: &str = "// [line ";
+
+/// What a script declares it does, read from its comments.
+#[derive(Default)]
+struct Expected {
+    output: Vec<String>,
+    /// Line of the annotation and message.
+    runtime_error: Option<(usize, String)>,
+    /// As `[line N] Error: message`.
+    compile_errors: Vec<String>,
+}
+
+/// A script that doesn't do what its comments say.
+pub struct Failure {
+    pub path: PathBuf,
+    /// Expected lines start with '-', what the script did instead with '+'.
+    pub diff: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct TestReport {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+/// Script output, kept where the runner can read it back.
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Capture {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.0.borrow())
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+}
+
+impl TestReport {
+    pub fn ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for TestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "FAIL {}", failure.path.display())?;
+            for line in &failure.diff {
+                writeln!(f, "    {}", line)?;
+            }
+        }
+        write!(f, "{} passed, {} failed", self.passed, self.failures.len())
+    }
+}
+
+fn expectations(source: &str) -> Expected {
+    let mut expected = Expected::default();
+    for (index, line) in source.lines().enumerate() {
+        let after = |marker: &str| line.find(marker).map(|i| &line[i + marker.len()..]);
+        if let Some(text) = after(EXPECT) {
+            expected.output.push(text.to_string());
+        } else if let Some(message) = after(EXPECT_RUNTIME_ERROR) {
+            expected.runtime_error = Some((index + 1, message.trim_end().to_string()));
+        } else if let Some(i) = line.find(EXPECT_ERROR) {
+            expected
+                .compile_errors
+                .push(line[i + 3..].trim_end().to_string());
+        }
+    }
+    expected
+}
+
+impl Expected {
+    fn is_empty(&self) -> bool {
+        self.output.is_empty() && self.runtime_error.is_none() && self.compile_errors.is_empty()
+    }
+}
+
+/// Runs every `.lox` file under `dir`, subdirectories included, and
+/// compares what it does with its `// expect` comments. Files without any,
+/// like modules imported by the tests, are not tests themselves.
+pub fn run_tests(dir: &Path, vm: bool) -> io::Result<TestReport> {
+    let mut report = TestReport::default();
+    for path in scripts(dir)? {
+        if expectations(&fs::read_to_string(&path)?).is_empty() {
+            continue;
+        }
+        let diff = check_on_thread(path.clone(), vm)?;
+        if diff.is_empty() {
+            report.passed += 1;
+        } else {
+            report.failures.push(Failure { path, diff });
+        }
+    }
+    Ok(report)
+}
+
+fn scripts(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+    let mut found = vec![];
+    for path in entries {
+        if path.is_dir() {
+            found.extend(scripts(&path)?);
+        } else if path.extension().is_some_and(|e| e == "lox") {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+/// Checks a script on a thread with room for the deepest recursion Lox
+/// allows, so a test of the call depth limit doesn't take the runner down.
+fn check_on_thread(path: PathBuf, vm: bool) -> io::Result<Vec<String>> {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            set_stack_limit(STACK_SIZE / 2);
+            check_script(&path, vm)
+        })?
+        .join()
+        .unwrap_or_else(|_| Ok(vec!["+ the interpreter panicked".to_string()]))
+}
+
+/// Runs the script at `path` in a fresh engine and returns how it differs
+/// from its annotations, nothing when it passes.
+pub fn check_script(path: &Path, vm: bool) -> io::Result<Vec<String>> {
+    let source = fs::read_to_string(path)?;
+    let expected = expectations(&source);
+    let mut lox = if vm { Lox::with_vm() } else { Lox::new() };
+    let output = Capture::default();
+    lox.set_output(output.clone());
+    let errors = lox
+        .run_script(&source, path.to_path_buf())
+        .err()
+        .unwrap_or_default();
+
+    let mut diff = vec![];
+    let compile_errors = errors
+        .iter()
+        .filter(|e| !e.is_runtime())
+        .map(|e| format!("[line {}] Error: {}", e.line(), e.message()))
+        .collect::<Vec<String>>();
+    diff_lines(&mut diff, &expected.compile_errors, &compile_errors);
+    let runtime_error = errors
+        .iter()
+        .find(|e| e.is_runtime())
+        .map(|e| format!("[line {}] runtime error: {}", script_line(e), e.message()));
+    let expected_error = expected
+        .runtime_error
+        .map(|(line, message)| format!("[line {}] runtime error: {}", line, message));
+    if runtime_error != expected_error {
+        diff.extend(expected_error.map(|e| format!("- {}", e)));
+        diff.extend(runtime_error.map(|e| format!("+ {}", e)));
+    }
+    diff_lines(&mut diff, &expected.output, &output.lines());
+    Ok(diff)
+}
+
+/// Line of the script a runtime error points to. An error inside a module
+/// is annotated where the script called into it, the outermost frame.
+fn script_line(err: &LoxError) -> usize {
+    match (err.file(), err.trace().last()) {
+        (Some(_), Some(frame)) => frame.line,
+        _ => err.line(),
+    }
+}
+
+/// Appends the lines missing from `actual` with '-' and the unexpected
+/// ones with '+', keeping the longest common subsequence unmarked.
+fn diff_lines(diff: &mut Vec<String>, expected: &[String], actual: &[String]) {
+    let (n, m) = (expected.len(), actual.len());
+    // common[i][j] is the length of the longest common subsequence of
+    // expected[i..] and actual[j..].
+    let mut common = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            diff.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+}
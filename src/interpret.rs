@@ -1,15 +1,32 @@
 use crate::ast::{
-    Ast, BinaryExpr, ExprStmt, GroupExpr, LiteralExpr, PrintStmt, Program, UnaryExpr, VarDecl,
+    Ast, BinaryExpr, ExprStmt, GroupExpr, ImportStmt, IndexExpr, IndexSetExpr, InterpolationExpr,
+    ListExpr, LiteralExpr, MapExpr, PrintStmt, Program, UnaryExpr, VarDecl,
 };
 
 use crate::environment::{Env, Environment};
 use crate::{
-    ast::{AssignExpr, Block, BreakStmt, FunCall, FunDecl, FunDef, IfStmt, ReturnStmt, WhileStmt},
-    function::{all_natives, Function, Implementation},
-    lox_error,
+    ast::{
+        AssignExpr, Block, BreakStmt, ClassDecl, CoalesceExpr, ConditionalExpr, ContinueStmt,
+        ForStmt, FunCall, FunDecl, FunDef, GetExpr, IfStmt, ReturnStmt, SetExpr, SuperExpr,
+        ThrowStmt, TryStmt, WhileStmt,
+    },
+    class::{Class, Instance, InstanceRef},
+    error::{Frame, LoxError, MAX_FRAMES},
+    exception::{caught_value, error_class, throw_error},
+    function::{all_natives, Arity, Function, Implementation, Runtime},
+    gc,
+    list::{List, ListRef},
+    map::{Map, MapRef},
+    module::{import, Loader, LoaderRef, ModuleRef},
+    output::{Output, OutputRef},
+    stack,
     token::{Token, TokenKind},
 };
-use std::fmt::Display;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::path::PathBuf;
+use std::rc::Rc;
 
 #[derive(PartialEq, Clone)]
 pub enum Value {
@@ -17,109 +34,169 @@ pub enum Value {
     String(String),
     Boolean(bool),
     Function(Function),
+    Class(Rc<Class>),
+    Instance(InstanceRef),
+    List(ListRef),
+    Map(MapRef),
+    Module(ModuleRef),
     Nil,
 }
 
 pub struct Interpretor {
     env: Env,
+    globals: Env,
+    frames: Vec<Frame>,
+    /// Pending break and continue, holding the label of the loop they
+    /// leave when they name one.
     breaking: Option<Token>,
+    continuing: Option<Token>,
     returning: Option<(Token, Value)>,
+    error_class: Rc<Class>,
+    loader: LoaderRef,
+    output: OutputRef,
+    /// File being run, imports are relative to it.
+    path: Option<PathBuf>,
 }
 
-pub fn interpret(ast: Ast) -> Option<Value> {
-    let mut interpretor = Interpretor::new();
-    for (name, nf) in all_natives() {
-        interpretor.env.borrow_mut().init(name, Value::Function(nf));
-    }
-
-    let rsl = ast.root().interpret(&mut interpretor).ok();
-    if let Some(tkn) = interpretor.breaking {
-        lox_error(tkn.line(), "break statement out of loop");
-        None
-    } else if let Some((tkn, _)) = interpretor.returning {
-        lox_error(tkn.line(), "return statement out of function");
-        None
-    } else {
-        rsl
-    }
+/// How a native function called by the tree-walker reaches back into it.
+struct NativeCall<'a> {
+    interpretor: &'a mut Interpretor,
+    paren: &'a Token,
 }
 
-pub fn check_arity(params: &Vec<String>, arg_count: usize) -> Option<usize> {
-    let pcount;
-    let err;
-
-    if params.last() == Some(&"".to_string()) {
-        pcount = params.len() - 1;
-        err = arg_count < pcount;
-    } else {
-        pcount = params.len();
-        err = arg_count != pcount;
+impl Runtime for NativeCall<'_> {
+    fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, LoxError> {
+        self.interpretor
+            .call_value(callee.clone(), args, self.paren)
     }
-    if err {
-        return Some(pcount);
+    fn error(&self, message: &str) -> LoxError {
+        LoxError::runtime(self.paren, message)
+    }
+    fn print(&mut self, text: &str) -> Result<(), LoxError> {
+        self.interpretor
+            .output
+            .borrow_mut()
+            .print(text)
+            .map_err(|message| self.error(&message))
     }
-    None
 }
 
 impl Interpretor {
-    fn new() -> Interpretor {
+    pub fn new() -> Interpretor {
+        Interpretor::with_loader(Loader::create(), Output::create(), None)
+    }
+    /// An interpretor with globals of its own, for a module loaded by
+    /// `loader`.
+    fn with_loader(loader: LoaderRef, output: OutputRef, path: Option<PathBuf>) -> Interpretor {
+        let globals = Environment::new(None);
+        for (name, nf) in all_natives() {
+            globals.borrow_mut().init(name, Value::Function(nf));
+        }
+        let error_class = error_class();
+        globals
+            .borrow_mut()
+            .init("Error".to_string(), Value::Class(error_class.clone()));
         Interpretor {
-            env: Environment::new(None),
+            env: globals.clone(),
+            globals,
+            frames: vec![],
             breaking: None,
+            continuing: None,
             returning: None,
+            error_class,
+            loader,
+            output,
+            path,
         }
     }
-    fn _env_global(env: Env) -> Env {
-        match env.borrow().parent().as_ref() {
-            None => env.clone(),
-            Some(p) => Self::_env_global(p.clone()),
-        }
+    pub fn output(&self) -> OutputRef {
+        self.output.clone()
+    }
+    /// Sets the file imports are relative to, returns the previous one.
+    pub fn set_path(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        std::mem::replace(&mut self.path, path)
+    }
+    /// Runs `ast` against the global environment of this interpretor, so
+    /// declarations persist between calls.
+    pub fn run(&mut self, ast: &Ast) -> Result<Value, LoxError> {
+        let rsl = stack::guarded(|| ast.root().interpret(self));
+        self.reset();
+        rsl
+    }
+    /// Forgets the state of a run that stopped on an error, pending jumps
+    /// and the labels they hold included.
+    fn reset(&mut self) {
+        self.breaking = None;
+        self.continuing = None;
+        self.returning = None;
+        self.frames.clear();
+        self.env = self.globals.clone();
     }
     fn env_global(&self) -> Env {
-        Self::_env_global(self.env.clone())
+        self.globals.clone()
     }
-    pub fn interpret_literal(&mut self, node: &LiteralExpr) -> Result<Value, ()> {
+    pub fn globals(&self) -> Env {
+        self.globals.clone()
+    }
+    fn lookup_variable(&self, name: &Token, depth: Option<usize>) -> Option<Value> {
+        match depth {
+            Some(d) => Environment::get_at(&self.env, d, name.text()),
+            None => Environment::root(&self.env).borrow().get(name.text()),
+        }
+    }
+    pub fn interpret_literal(&mut self, node: &LiteralExpr) -> Result<Value, LoxError> {
         match node.token().kind() {
             TokenKind::Nil => Ok(Value::Nil),
             TokenKind::Number => match node.token().text().parse::<f64>() {
                 Ok(num) => Ok(Value::Number(num)),
-                Err(_) => {
-                    crate::lox_error(
-                        node.token().line(),
-                        &format!("invalid number ({})", node.token().text()),
-                    );
-                    Err(())
-                }
+                Err(_) => Err(LoxError::runtime(
+                    node.token(),
+                    &format!("invalid number ({})", node.token().text()),
+                )),
             },
             TokenKind::String => Ok(Value::String(
                 node.token().text()[1..node.token().text().len() - 1].to_owned(),
             )),
             TokenKind::True => Ok(Value::Boolean(true)),
             TokenKind::False => Ok(Value::Boolean(false)),
-            TokenKind::Identifier => match self.env.borrow_mut().get(node.token().text()) {
-                Some(v) => Ok(v.clone()),
-                None => {
-                    lox_error(
-                        node.token().line(),
-                        format!("undefind variable '{}'", node.token().text()).as_str(),
-                    );
-                    Err(())
-                }
+            TokenKind::Identifier => match self.lookup_variable(node.token(), node.depth()) {
+                Some(v) => Ok(v),
+                None => Err(LoxError::runtime(
+                    node.token(),
+                    &format!("undefind variable '{}'", node.token().text()),
+                )),
+            },
+            TokenKind::This => match self.lookup_variable(node.token(), node.depth()) {
+                Some(v) => Ok(v),
+                None => Err(LoxError::runtime(
+                    node.token(),
+                    "'this' used outside of a class",
+                )),
             },
-            _ => Err(()),
+            _ => Err(LoxError::runtime(node.token(), "invalid literal")),
         }
     }
-    pub fn interpret_group(&mut self, node: &GroupExpr) -> Result<Value, ()> {
+    pub fn interpret_group(&mut self, node: &GroupExpr) -> Result<Value, LoxError> {
         node.expr().interpret(self)
     }
-    pub fn interpret_assignment(&mut self, node: &AssignExpr) -> Result<Value, ()> {
+    pub fn interpret_assignment(&mut self, node: &AssignExpr) -> Result<Value, LoxError> {
         let value = node.expr().interpret(self)?;
-        self.env
-            .borrow_mut()
-            .assign(node.variable().text().clone(), value.clone());
+        let name = node.variable().text().clone();
+        let assigned = match node.depth() {
+            Some(d) => Environment::assign_at(&self.env, d, name, value.clone()),
+            None => Environment::root(&self.env)
+                .borrow_mut()
+                .assign(name, value.clone()),
+        };
+        if !assigned {
+            return Err(LoxError::runtime(
+                node.variable(),
+                &format!("undefind variable '{}'", node.variable().text()),
+            ));
+        }
         Ok(value)
     }
-    pub fn interpret_if_stmt(&mut self, node: &IfStmt) -> Result<Value, ()> {
+    pub fn interpret_if_stmt(&mut self, node: &IfStmt) -> Result<Value, LoxError> {
         let condition = node.expr().interpret(self)?;
         if condition.truth() {
             node.stmt().interpret(self)?;
@@ -130,21 +207,64 @@ impl Interpretor {
         }
         Ok(Value::Nil)
     }
-    pub fn interpret_while_stmt(&mut self, node: &WhileStmt) -> Result<Value, ()> {
+    pub fn interpret_while_stmt(&mut self, node: &WhileStmt) -> Result<Value, LoxError> {
         while node.expr().interpret(self)?.truth() {
             node.stmt().interpret(self)?;
-            if let Some(_) = self.breaking {
-                self.breaking = None;
+            if self.end_iteration(node.label()) {
+                break;
+            }
+        }
+        Ok(Value::Nil)
+    }
+    /// Settles the break or continue pending after an iteration of the loop
+    /// labeled `label`, returns whether the loop stops.
+    fn end_iteration(&mut self, label: Option<&Token>) -> bool {
+        // an unlabeled jump holds its keyword
+        let leaves = |jump: &Token| match jump.kind() {
+            TokenKind::Identifier => label.is_some_and(|l| l.text() == jump.text()),
+            _ => true,
+        };
+        if self.continuing.as_ref().is_some_and(leaves) {
+            self.continuing = None;
+        }
+        if self.breaking.as_ref().is_some_and(leaves) {
+            self.breaking = None;
+            return true;
+        }
+        self.breaking.is_some() || self.continuing.is_some() || self.returning.is_some()
+    }
+    pub fn interpret_for_stmt(&mut self, node: &ForStmt) -> Result<Value, LoxError> {
+        let parent = self.env.clone();
+        self.env = Environment::new(Some(parent.clone()));
+        if let Some(init) = node.initialize() {
+            init.interpret(self)?;
+        }
+        loop {
+            if let Some(condition) = node.condition() {
+                if !condition.interpret(self)?.truth() {
+                    break;
+                }
+            }
+            node.stmt().interpret(self)?;
+            if self.end_iteration(node.label()) {
                 break;
             }
+            if let Some(increment) = node.increment() {
+                increment.interpret(self)?;
+            }
         }
+        self.env = parent;
+        Ok(Value::Nil)
+    }
+    pub fn interpret_break_stmt(&mut self, node: &BreakStmt) -> Result<Value, LoxError> {
+        self.breaking = Some(node.label().unwrap_or(node.token()).clone());
         Ok(Value::Nil)
     }
-    pub fn interpret_break_stmt(&mut self, node: &BreakStmt) -> Result<Value, ()> {
-        self.breaking = Some(node.token().clone());
+    pub fn interpret_continue_stmt(&mut self, node: &ContinueStmt) -> Result<Value, LoxError> {
+        self.continuing = Some(node.label().unwrap_or(node.token()).clone());
         Ok(Value::Nil)
     }
-    pub fn interpret_return_stmt(&mut self, node: &ReturnStmt) -> Result<Value, ()> {
+    pub fn interpret_return_stmt(&mut self, node: &ReturnStmt) -> Result<Value, LoxError> {
         let value = match node.expr() {
             Some(e) => e.interpret(self)?,
             None => Value::Nil,
@@ -152,33 +272,76 @@ impl Interpretor {
         self.returning = Some((node.token().clone(), value));
         Ok(Value::Nil)
     }
-    pub fn interpret_unary(&mut self, node: &UnaryExpr) -> Result<Value, ()> {
+    pub fn interpret_throw_stmt(&mut self, node: &ThrowStmt) -> Result<Value, LoxError> {
+        let value = node.expr().interpret(self)?;
+        Err(throw_error(node.token(), value))
+    }
+    pub fn interpret_try_stmt(&mut self, node: &TryStmt) -> Result<Value, LoxError> {
+        // Blocks left by an error don't restore the environment themselves.
+        let env = self.env.clone();
+        let mut rsl = node.body().interpret(self);
+        if let (Err(err), Some((param, block))) = (&rsl, node.catch()) {
+            if err.is_runtime() {
+                let value = caught_value(err, &self.error_class);
+                self.env = Environment::new(Some(env.clone()));
+                self.env.borrow_mut().init(param.text().clone(), value);
+                rsl = block.interpret(self);
+            }
+        }
+        self.env = env.clone();
+        if let Some(finally) = node.finally() {
+            // A pending break, continue or return resumes after the
+            // finally block, unless the block jumps away or fails itself.
+            let breaking = self.breaking.take();
+            let continuing = self.continuing.take();
+            let returning = self.returning.take();
+            let finished = finally.interpret(self);
+            self.env = env;
+            finished?;
+            if self.breaking.is_none() && self.continuing.is_none() && self.returning.is_none() {
+                self.breaking = breaking;
+                self.continuing = continuing;
+                self.returning = returning;
+            } else {
+                return Ok(Value::Nil);
+            }
+        }
+        rsl
+    }
+    pub fn interpret_conditional(&mut self, node: &ConditionalExpr) -> Result<Value, LoxError> {
+        if node.condition().interpret(self)?.truth() {
+            node.then().interpret(self)
+        } else {
+            node.otherwise().interpret(self)
+        }
+    }
+    pub fn interpret_coalesce(&mut self, node: &CoalesceExpr) -> Result<Value, LoxError> {
+        match node.lexpr().interpret(self)? {
+            Value::Nil => node.rexpr().interpret(self),
+            value => Ok(value),
+        }
+    }
+    pub fn interpret_unary(&mut self, node: &UnaryExpr) -> Result<Value, LoxError> {
         if node.token().kind() == TokenKind::Bang {
             Ok(Value::Boolean(!node.expr().interpret(self)?.truth()))
         } else {
             match node.expr().interpret(self)? {
                 Value::Number(num) => Ok(Value::Number(-num)),
-                _ => {
-                    crate::lox_error(node.token().line(), "expected number after '-'");
-                    Err(())
-                }
+                _ => Err(LoxError::runtime(node.token(), "expected number after '-'")),
             }
         }
     }
-    pub fn interpret_plus(&mut self, node: &BinaryExpr) -> Result<Value, ()> {
+    pub fn interpret_plus(&mut self, node: &BinaryExpr) -> Result<Value, LoxError> {
         match (node.lexpr().interpret(self)?, node.rexpr().interpret(self)?) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
             (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
-            _ => {
-                crate::lox_error(
-                    node.token().line(),
-                    "operator '+' can only be used on 2 numbers or 2 strings",
-                );
-                Err(())
-            }
+            _ => Err(LoxError::runtime(
+                node.token(),
+                "operator '+' can only be used on 2 numbers or 2 strings",
+            )),
         }
     }
-    pub fn interpret_math(&mut self, node: &BinaryExpr) -> Result<Value, ()> {
+    pub fn interpret_math(&mut self, node: &BinaryExpr) -> Result<Value, LoxError> {
         match (node.lexpr().interpret(self)?, node.rexpr().interpret(self)?) {
             (Value::Number(a), Value::Number(b)) => match node.token().kind() {
                 TokenKind::Star => Ok(Value::Number(a * b)),
@@ -188,38 +351,35 @@ impl Interpretor {
                 TokenKind::LessEqual => Ok(Value::Boolean(a <= b)),
                 TokenKind::Less => Ok(Value::Boolean(a < b)),
                 TokenKind::Greater => Ok(Value::Boolean(a > b)),
-                _ => Err(()),
+                _ => Err(LoxError::runtime(node.token(), "invalid operator")),
             },
-            _ => {
-                crate::lox_error(
-                    node.token().line(),
-                    "arithmatic operators can only be used on numbers",
-                );
-                Err(())
-            }
+            _ => Err(LoxError::runtime(
+                node.token(),
+                "arithmatic operators can only be used on numbers",
+            )),
         }
     }
-    pub fn interpret_and(&mut self, node: &BinaryExpr) -> Result<Value, ()> {
+    pub fn interpret_and(&mut self, node: &BinaryExpr) -> Result<Value, LoxError> {
         let left = node.lexpr().interpret(self)?;
         if !left.truth() {
             return Ok(Value::Boolean(false));
         }
         node.rexpr().interpret(self)
     }
-    pub fn interpret_or(&mut self, node: &BinaryExpr) -> Result<Value, ()> {
+    pub fn interpret_or(&mut self, node: &BinaryExpr) -> Result<Value, LoxError> {
         let left = node.lexpr().interpret(self)?;
         if left.truth() {
             return Ok(Value::Boolean(true));
         }
         node.rexpr().interpret(self)
     }
-    pub fn interpret_binary(&mut self, node: &BinaryExpr) -> Result<Value, ()> {
+    pub fn interpret_binary(&mut self, node: &BinaryExpr) -> Result<Value, LoxError> {
         match node.token().kind() {
             TokenKind::EqualEqual => Ok(Value::Boolean(
-                node.lexpr().interpret(self) == node.rexpr().interpret(self),
+                node.lexpr().interpret(self)? == node.rexpr().interpret(self)?,
             )),
             TokenKind::BangEqual => Ok(Value::Boolean(
-                node.lexpr().interpret(self) != node.rexpr().interpret(self),
+                node.lexpr().interpret(self)? != node.rexpr().interpret(self)?,
             )),
             TokenKind::Or => self.interpret_or(node),
             TokenKind::And => self.interpret_and(node),
@@ -227,16 +387,19 @@ impl Interpretor {
             _ => self.interpret_math(node),
         }
     }
-    pub fn interpret_print_stmt(&mut self, node: &PrintStmt) -> Result<Value, ()> {
+    pub fn interpret_print_stmt(&mut self, node: &PrintStmt) -> Result<Value, LoxError> {
         let value = node.expr().interpret(self)?;
-        println!("{}", value);
+        self.output
+            .borrow_mut()
+            .print(&value.to_string())
+            .map_err(|message| LoxError::runtime(node.token(), &message))?;
         Ok(Value::Nil)
     }
-    pub fn interpret_expr_stmt(&mut self, node: &ExprStmt) -> Result<Value, ()> {
+    pub fn interpret_expr_stmt(&mut self, node: &ExprStmt) -> Result<Value, LoxError> {
         node.expr().interpret(self)?;
         Ok(Value::Nil)
     }
-    pub fn interpret_var_decl(&mut self, node: &VarDecl) -> Result<Value, ()> {
+    pub fn interpret_var_decl(&mut self, node: &VarDecl) -> Result<Value, LoxError> {
         let value = match node.expr() {
             Some(e) => e.interpret(self)?,
             None => Value::Nil,
@@ -247,11 +410,37 @@ impl Interpretor {
 
         Ok(Value::Nil)
     }
-    pub fn interpret_fun_decl(&mut self, node: &FunDecl) -> Result<Value, ()> {
+    pub fn interpret_import_stmt(&mut self, node: &ImportStmt) -> Result<Value, LoxError> {
+        let path = node.path().text();
+        let importer = self
+            .loader
+            .borrow()
+            .file_of(&Environment::root(&self.env))
+            .or_else(|| self.path.clone());
+        let loader = self.loader.clone();
+        let output = self.output.clone();
+        let module = import(
+            &self.loader,
+            importer.as_deref(),
+            node.path(),
+            &path[1..path.len() - 1],
+            |ast, path| {
+                let mut interpretor = Interpretor::with_loader(loader, output, Some(path));
+                interpretor.run(ast)?;
+                Ok(interpretor.globals())
+            },
+        )?;
+        self.env
+            .borrow_mut()
+            .init(node.name().text().clone(), Value::Module(module));
+        Ok(Value::Nil)
+    }
+    pub fn interpret_fun_decl(&mut self, node: &FunDecl) -> Result<Value, LoxError> {
         let name = node.name().text().clone();
         self.env.borrow_mut().init(
             name.clone(),
             Value::Function(Function::create(
+                name.clone(),
                 Implementation::LoxImpl(node.block().clone()),
                 node.params().iter().map(|t| t.text().clone()).collect(),
                 Some(self.env.clone()),
@@ -259,84 +448,318 @@ impl Interpretor {
         );
         Ok(Value::Nil)
     }
-    pub fn interpret_fun_def(&mut self, node: &FunDef) -> Result<Value, ()> {
+    pub fn interpret_fun_def(&mut self, node: &FunDef) -> Result<Value, LoxError> {
         Ok(Value::Function(Function::create(
+            "<anonymous>".to_string(),
             Implementation::LoxImpl(node.block().clone()),
             node.params().iter().map(|t| t.text().clone()).collect(),
             Some(self.env.clone()),
         )))
     }
-    pub fn interpret_fun_call(&mut self, node: &FunCall) -> Result<Value, ()> {
-        let line = node.line();
+    pub fn interpret_fun_call(&mut self, node: &FunCall) -> Result<Value, LoxError> {
         let callee = node.callee().interpret(self)?;
-        let callee = match callee {
-            Value::Function(fun) => fun,
-            _ => {
-                lox_error(line, format!("{} is not callable", callee).as_str());
-                return Err(());
-            }
-        };
-        if let Some(pcount) = check_arity(callee.params(), node.args().len()) {
-            lox_error(
-                line,
-                format!(
-                    "invalid number of arguments ({}) passed to function which takes {} params",
-                    node.args().len(),
-                    pcount,
-                )
-                .as_str(),
-            );
-            return Err(());
-        }
-        let new_env = callee.closure().unwrap_or_else(|| self.env_global());
-        let new_env = Environment::new(Some(new_env));
         let mut args = vec![];
         for a in node.args() {
             args.push(a.interpret(self)?);
         }
+        self.call_value(callee, args, node.paren())
+    }
+    /// Calls `callee` on behalf of the host, `token` stands for the call
+    /// site.
+    pub fn call(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        token: &Token,
+    ) -> Result<Value, LoxError> {
+        let rsl = stack::guarded(|| self.call_value(callee, args, token));
+        self.reset();
+        rsl
+    }
+    fn call_value(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, LoxError> {
+        gc::maybe_collect();
+        match callee {
+            Value::Function(fun) => self.call_function(fun, args, paren),
+            Value::Class(class) => {
+                let instance = Value::Instance(Instance::create(class.clone()));
+                match class.find_method("init") {
+                    Some(init) => {
+                        self.call_function(init.bind(instance.clone()), args, paren)?;
+                    }
+                    None => {
+                        if let Some(pcount) = Arity::Fixed(0).check(args.len()) {
+                            return Err(Self::arity_error(paren, args.len(), pcount));
+                        }
+                    }
+                }
+                Ok(instance)
+            }
+            _ => Err(LoxError::runtime(
+                paren,
+                &format!("{} is not callable", callee),
+            )),
+        }
+    }
+    fn call_function(
+        &mut self,
+        callee: Function,
+        args: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, LoxError> {
+        if let Some(pcount) = callee.arity().check(args.len()) {
+            return Err(Self::arity_error(paren, args.len(), pcount));
+        }
+        if self.frames.len() >= MAX_FRAMES || stack::exhausted() {
+            return Err(LoxError::runtime(paren, "stack overflow")
+                .with_trace(self.frames.iter().rev().cloned().collect()));
+        }
+        self.frames
+            .push(Frame::new(callee.name().clone(), paren.call_line()));
+        let rsl = self.execute_function(&callee, args, paren).map_err(|e| {
+            e.with_trace(self.frames.iter().rev().cloned().collect())
+                .placed_in(self.file_of(&callee))
+        });
+        self.frames.pop();
+        rsl
+    }
+    /// File the errors raised by `callee` itself are located in, that of
+    /// its body, or of the call for native functions.
+    fn file_of(&self, callee: &Function) -> Option<PathBuf> {
+        let env = match (callee.code(), callee.closure()) {
+            (Implementation::LoxImpl(_), Some(closure)) => closure,
+            _ => self.env.clone(),
+        };
+        self.loader.borrow().file_of(&Environment::root(&env))
+    }
+    fn execute_function(
+        &mut self,
+        callee: &Function,
+        args: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, LoxError> {
+        let new_env = callee.closure().unwrap_or_else(|| self.env_global());
+        let new_env = Environment::new(Some(new_env));
         match callee.code() {
-            Implementation::NativeImpl(nf) => Ok(nf(args)?),
+            Implementation::NativeImpl(nf, _) => nf(
+                &mut NativeCall {
+                    interpretor: self,
+                    paren,
+                },
+                callee.receiver().into_iter().chain(args).collect(),
+            ),
+            Implementation::Compiled(_) => Err(LoxError::runtime(
+                paren,
+                "function belongs to another engine",
+            )),
             Implementation::LoxImpl(lf) => {
                 let prev = self.env.clone();
                 self.env = new_env;
                 for (p, a) in callee.params().iter().zip(args.iter()) {
                     self.env.borrow_mut().init(p.clone(), a.clone())
                 }
-                lf.interpret(self)?;
+                let rsl = lf.interpret(self);
                 self.env = prev;
+                rsl?;
 
-                if let Some((_, value)) = self.returning.clone() {
-                    self.returning = None;
-                    Ok(value)
+                let value = match self.returning.take() {
+                    Some((_, value)) => value,
+                    None => Value::Nil,
+                };
+                if callee.is_initializer() {
+                    Ok(callee
+                        .closure()
+                        .and_then(|c| c.borrow().get(&"this".to_string()))
+                        .unwrap_or(Value::Nil))
                 } else {
-                    Ok(Value::Nil)
+                    Ok(value)
                 }
             }
         }
     }
+    pub fn arity_error(paren: &Token, arg_count: usize, pcount: usize) -> LoxError {
+        LoxError::runtime(
+            paren,
+            &format!(
+                "invalid number of arguments ({}) passed to function which takes {} params",
+                arg_count, pcount,
+            ),
+        )
+    }
+    pub fn interpret_class_decl(&mut self, node: &ClassDecl) -> Result<Value, LoxError> {
+        let superclass = match node.superclass() {
+            Some(s) => match s.interpret(self)? {
+                Value::Class(c) => Some(c),
+                _ => return Err(LoxError::runtime(node.name(), "superclass must be a class")),
+            },
+            None => None,
+        };
+        let closure = match &superclass {
+            Some(s) => {
+                let env = Environment::new(Some(self.env.clone()));
+                env.borrow_mut()
+                    .init("super".to_string(), Value::Class(s.clone()));
+                env
+            }
+            None => self.env.clone(),
+        };
+        let mut methods = HashMap::new();
+        for m in node.methods() {
+            let name = m.name().text().clone();
+            let initializer = name == "init";
+            methods.insert(
+                name.clone(),
+                Function::create_method(
+                    name,
+                    Implementation::LoxImpl(m.block().clone()),
+                    m.params().iter().map(|t| t.text().clone()).collect(),
+                    closure.clone(),
+                    initializer,
+                ),
+            );
+        }
+        let class = Class::create(node.name().text().clone(), superclass, methods);
+        self.env
+            .borrow_mut()
+            .init(node.name().text().clone(), Value::Class(class));
+        Ok(Value::Nil)
+    }
+    pub fn interpret_get_expr(&mut self, node: &GetExpr) -> Result<Value, LoxError> {
+        let object = node.object().interpret(self)?;
+        let instance = match &object {
+            Value::Instance(instance) => instance.clone(),
+            Value::Module(module) => {
+                return module
+                    .get(node.name().text())
+                    .map_err(|message| LoxError::runtime(node.name(), &message))
+            }
+            _ => {
+                return Err(LoxError::runtime(
+                    node.name(),
+                    "only instances have properties",
+                ))
+            }
+        };
+        let name = node.name().text();
+        if let Some(value) = instance.borrow().field(name) {
+            return Ok(value);
+        }
+        let method = instance.borrow().class().find_method(name);
+        match method {
+            Some(method) => Ok(Value::Function(method.bind(object))),
+            None => Err(LoxError::runtime(
+                node.name(),
+                &format!("undefined property '{}'", name),
+            )),
+        }
+    }
+    pub fn interpret_set_expr(&mut self, node: &SetExpr) -> Result<Value, LoxError> {
+        let object = node.object().interpret(self)?;
+        let instance = match object {
+            Value::Instance(instance) => instance,
+            _ => return Err(LoxError::runtime(node.name(), "only instances have fields")),
+        };
+        let value = node.expr().interpret(self)?;
+        instance
+            .borrow_mut()
+            .set_field(node.name().text().clone(), value.clone());
+        Ok(value)
+    }
 
-    pub fn interpret_program(&mut self, node: &Program) -> Result<Value, ()> {
+    pub fn interpret_super_expr(&mut self, node: &SuperExpr) -> Result<Value, LoxError> {
+        let depth = node.depth().expect("resolver sets the depth of super");
+        let superclass = match Environment::get_at(&self.env, depth, &"super".to_string()) {
+            Some(Value::Class(c)) => c,
+            _ => {
+                return Err(LoxError::runtime(
+                    node.keyword(),
+                    "'super' used outside of a subclass",
+                ))
+            }
+        };
+        let instance =
+            Environment::get_at(&self.env, depth - 1, &"this".to_string()).unwrap_or(Value::Nil);
+        match superclass.find_method(node.method().text()) {
+            Some(method) => Ok(Value::Function(method.bind(instance))),
+            None => Err(LoxError::runtime(
+                node.method(),
+                &format!("undefined property '{}'", node.method().text()),
+            )),
+        }
+    }
+
+    pub fn interpret_list_expr(&mut self, node: &ListExpr) -> Result<Value, LoxError> {
+        let mut items = vec![];
+        for i in node.items() {
+            items.push(i.interpret(self)?);
+        }
+        Ok(Value::List(List::create(items)))
+    }
+    pub fn interpret_interpolation_expr(
+        &mut self,
+        node: &InterpolationExpr,
+    ) -> Result<Value, LoxError> {
+        let mut text = node.strings()[0].clone();
+        for (expr, string) in node.exprs().iter().zip(&node.strings()[1..]) {
+            text.push_str(&expr.interpret(self)?.to_string());
+            text.push_str(string);
+        }
+        Ok(Value::String(text))
+    }
+    pub fn interpret_map_expr(&mut self, node: &MapExpr) -> Result<Value, LoxError> {
+        let map = Map::create();
+        for (k, v) in node.entries() {
+            let key = k.interpret(self)?;
+            let value = v.interpret(self)?;
+            map.borrow_mut()
+                .set(&key, value)
+                .map_err(|message| LoxError::runtime(node.brace(), &message))?;
+        }
+        Ok(Value::Map(map))
+    }
+    pub fn interpret_index_expr(&mut self, node: &IndexExpr) -> Result<Value, LoxError> {
+        let object = node.object().interpret(self)?;
+        let index = node.index().interpret(self)?;
+        object
+            .get_index(&index)
+            .map_err(|message| LoxError::runtime(node.bracket(), &message))
+    }
+    pub fn interpret_index_set_expr(&mut self, node: &IndexSetExpr) -> Result<Value, LoxError> {
+        let object = node.object().interpret(self)?;
+        let index = node.index().interpret(self)?;
+        let value = node.expr().interpret(self)?;
+        object
+            .set_index(&index, value.clone())
+            .map_err(|message| LoxError::runtime(node.bracket(), &message))?;
+        Ok(value)
+    }
+
+    pub fn interpret_program(&mut self, node: &Program) -> Result<Value, LoxError> {
         for s in node.decs() {
             s.interpret(self)?;
-            if let Some(_) = self.breaking {
+            if self.breaking.is_some() {
                 break;
             }
-            if let Some(_) = self.returning {
+            if self.returning.is_some() {
                 break;
             }
         }
         Ok(Value::Nil)
     }
-    pub fn interpret_block(&mut self, node: &Block) -> Result<Value, ()> {
+    pub fn interpret_block(&mut self, node: &Block) -> Result<Value, LoxError> {
         let parent = self.env.clone();
         let branch = Environment::new(Some(parent.clone()));
         self.env = branch;
         for s in node.decs() {
             s.interpret(self)?;
-            if let Some(_) = self.breaking {
+            if self.breaking.is_some() || self.continuing.is_some() {
                 break;
             }
-            if let Some(_) = self.returning {
+            if self.returning.is_some() {
                 break;
             }
         }
@@ -346,15 +769,50 @@ impl Interpretor {
 }
 
 impl Value {
-    fn truth(&self) -> bool {
-        if *self == Value::Nil || *self == Value::Boolean(false) {
-            false
-        } else {
-            true
+    pub fn truth(&self) -> bool {
+        !(*self == Value::Nil || *self == Value::Boolean(false))
+    }
+    /// Reads `self[index]`, shared by both backends.
+    pub fn get_index(&self, index: &Value) -> Result<Value, String> {
+        match self {
+            Value::List(list) => list.borrow().get(index),
+            Value::Map(map) => map.borrow().get(index),
+            _ => Err("only lists and maps can be indexed".to_string()),
+        }
+    }
+    pub fn set_index(&self, index: &Value, value: Value) -> Result<(), String> {
+        match self {
+            Value::List(list) => list.borrow_mut().set(index, value),
+            Value::Map(map) => map.borrow_mut().set(index, value),
+            _ => Err("only lists and maps can be indexed".to_string()),
         }
     }
 }
 
+impl Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+thread_local! {
+    /// Lists and maps whose `Display` is running.
+    static DISPLAYING: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+}
+
+/// Shows the list or map at `address` with `show`, or as `cycle` when it is
+/// already being shown further up, so a container holding itself doesn't
+/// recurse forever.
+pub fn display_once(address: usize, cycle: &str, show: impl FnOnce() -> String) -> String {
+    if DISPLAYING.with(|d| d.borrow().contains(&address)) {
+        return cycle.to_string();
+    }
+    DISPLAYING.with(|d| d.borrow_mut().push(address));
+    let rep = show();
+    DISPLAYING.with(|d| d.borrow_mut().pop());
+    rep
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let rep = match self {
@@ -363,9 +821,16 @@ impl Display for Value {
             Value::Nil => String::from("NIL"),
             Value::Boolean(b) => b.to_string(),
             Value::Function(f) => match f.code() {
-                Implementation::NativeImpl(_) => "[Native Function]".to_string(),
-                Implementation::LoxImpl(_) => "[Function]".to_string(),
+                Implementation::NativeImpl(..) => "[Native Function]".to_string(),
+                Implementation::LoxImpl(_) | Implementation::Compiled(_) => {
+                    "[Function]".to_string()
+                }
             },
+            Value::Class(c) => format!("[Class {}]", c.name()),
+            Value::Instance(i) => format!("[Instance {}]", i.borrow().class().name()),
+            Value::List(l) => l.borrow().to_string(),
+            Value::Map(m) => m.borrow().to_string(),
+            Value::Module(m) => format!("[Module {}]", m.name()),
         };
         write!(f, "{}", rep)
     }
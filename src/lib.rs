@@ -0,0 +1,241 @@
+//! Lox interpreter that can be embedded in other programs through the
+//! [`Lox`] engine.
+
+mod ast;
+mod chunk;
+mod class;
+mod compile;
+mod environment;
+mod error;
+mod exception;
+mod function;
+mod gc;
+mod golden;
+mod interpret;
+mod list;
+mod map;
+mod module;
+mod output;
+mod parse;
+mod resolve;
+mod scan;
+mod stack;
+mod token;
+mod vm;
+
+use std::{io::Write, path::PathBuf, rc::Rc};
+
+use ast::{Ast, AstNodeKind};
+use function::Function;
+use interpret::Interpretor;
+use module::Loader;
+use output::{Output, OutputRef};
+use parse::{parse_repl, parse_source};
+use resolve::resolve;
+use scan::scan;
+use token::Token;
+use vm::Vm;
+
+pub use error::{Frame, LoxError};
+pub use function::{Arity, Runtime};
+pub use gc::GcStats;
+pub use golden::{check_script, run_tests, Failure, TestReport};
+pub use interpret::Value;
+pub use list::List;
+pub use map::Map;
+
+/// Native stack for a thread running deeply recursive scripts, enough for
+/// the tree-walker to reach the call depth limit in a debug build. See
+/// [`set_stack_limit`].
+pub const STACK_SIZE: usize = 64 << 20;
+
+enum Backend {
+    Tree(Box<Interpretor>),
+    Bytecode(Vm),
+}
+
+/// A Lox session. Globals persist from one evaluation to the next.
+pub struct Lox {
+    backend: Backend,
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Lox::new()
+    }
+}
+
+impl Lox {
+    /// An engine walking the syntax tree. Each Lox call recurses on the
+    /// native stack, [`set_stack_limit`] bounds how deep that goes.
+    pub fn new() -> Lox {
+        Lox {
+            backend: Backend::Tree(Box::new(Interpretor::new())),
+        }
+    }
+    /// An engine compiling to bytecode for the virtual machine.
+    pub fn with_vm() -> Lox {
+        Lox {
+            backend: Backend::Bytecode(Vm::new(Loader::create(), Output::create(), None)),
+        }
+    }
+    /// Runs `source`. When it is a bare expression, with or without a
+    /// trailing ';', its value is returned.
+    pub fn eval(&mut self, source: &str) -> Result<Option<Value>, Vec<LoxError>> {
+        let ast = parse_repl(scan(source.chars().collect())?)?;
+        resolve(&ast)?;
+        let value = self.run(&ast).map_err(|e| vec![e])?;
+        match ast.root().kind() {
+            AstNodeKind::Program => Ok(None),
+            _ => Ok(Some(value)),
+        }
+    }
+    /// Runs the script `source` read from `path`, imports are relative to
+    /// it while it runs.
+    pub fn run_script(&mut self, source: &str, path: PathBuf) -> Result<(), Vec<LoxError>> {
+        let ast = parse_source(scan(source.chars().collect())?)?;
+        resolve(&ast)?;
+        let previous = self.set_path(Some(path));
+        let rsl = self.run(&ast);
+        self.set_path(previous);
+        rsl.map_err(|e| vec![e])?;
+        Ok(())
+    }
+    fn set_path(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        match &mut self.backend {
+            Backend::Tree(interpretor) => interpretor.set_path(path),
+            Backend::Bytecode(vm) => vm.set_path(path),
+        }
+    }
+    fn run(&mut self, ast: &Ast) -> Result<Value, LoxError> {
+        match &mut self.backend {
+            Backend::Tree(interpretor) => interpretor.run(ast),
+            Backend::Bytecode(vm) => vm.run(ast),
+        }
+    }
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals_env().borrow().get(&name.to_string())
+    }
+    /// Defines or overwrites a global variable.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals_env()
+            .borrow_mut()
+            .init(name.to_string(), value);
+    }
+    /// All global bindings, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.globals_env().borrow().bindings()
+    }
+    /// Defines a global function implemented by the host. `native` can
+    /// call back into Lox through its `Runtime`, and capture whatever state
+    /// it needs.
+    pub fn define_native<F>(&mut self, name: &str, arity: Arity, native: F)
+    where
+        F: Fn(&mut dyn Runtime, Vec<Value>) -> Result<Value, LoxError> + 'static,
+    {
+        let function = Function::native(name.to_string(), arity, Rc::new(native));
+        self.set_global(name, Value::Function(function));
+    }
+    /// Sends what scripts print to `out` instead of stdout.
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.output().borrow_mut().set_out(Box::new(out));
+    }
+    /// Sends the errors written by `report_errors` to `err` instead of
+    /// stderr.
+    pub fn set_error_output(&mut self, err: impl Write + 'static) {
+        self.output().borrow_mut().set_err(Box::new(err));
+    }
+    /// Writes `errors` to the error output, each with its stack trace and
+    /// the line of `source`, or of the module, it points to. Runs of the
+    /// same frame in a trace are written once.
+    pub fn report_errors(&mut self, source: &str, errors: &[LoxError]) {
+        let output = self.output();
+        let mut output = output.borrow_mut();
+        for err in errors {
+            output.error(&err.to_string());
+            // errors inside a function of a module point into its file
+            let text = match err.file() {
+                Some(file) => std::fs::read_to_string(file).unwrap_or_default(),
+                None => source.to_string(),
+            };
+            let lines = text.lines().collect::<Vec<&str>>();
+            if let Some(line) = err.line().checked_sub(1).and_then(|l| lines.get(l)) {
+                let column = err.column() - 1;
+                let width = (err.span().end - err.span().start)
+                    .min(line.chars().count().saturating_sub(column))
+                    .max(1);
+                output.error(&format!("    {}", line));
+                output.error(&format!("    {}{}", " ".repeat(column), "^".repeat(width)));
+            }
+            // a runaway recursion repeats one frame thousands of times
+            for run in err.trace().chunk_by(|a, b| a == b) {
+                output.error(&format!("  {}", run[0]));
+                if run.len() > 1 {
+                    output.error(&format!(
+                        "  ... previous frame repeated {} more times",
+                        run.len() - 1
+                    ));
+                }
+            }
+        }
+    }
+    fn output(&self) -> OutputRef {
+        match &self.backend {
+            Backend::Tree(interpretor) => interpretor.output(),
+            Backend::Bytecode(vm) => vm.output(),
+        }
+    }
+    fn globals_env(&self) -> environment::Env {
+        match &self.backend {
+            Backend::Tree(interpretor) => interpretor.globals(),
+            Backend::Bytecode(vm) => vm.globals(),
+        }
+    }
+    /// Calls a function or class with `args`, errors are reported at the
+    /// first line.
+    pub fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, LoxError> {
+        let token = Token::host(callee.to_string());
+        match &mut self.backend {
+            Backend::Tree(interpretor) => interpretor.call(callee.clone(), args, &token),
+            Backend::Bytecode(vm) => vm.call(callee.clone(), args, &token),
+        }
+    }
+}
+
+/// Whether `source` stops in the middle of a declaration, e.g. inside a
+/// string or a block, so an interactive prompt should keep reading lines.
+pub fn is_incomplete(source: &str) -> bool {
+    scan::is_incomplete(source.chars().collect())
+}
+
+/// One line per token of `source`, with its position and kind.
+pub fn tokens(source: &str) -> Result<Vec<String>, Vec<LoxError>> {
+    Ok(scan(source.chars().collect())?
+        .iter()
+        .map(|t| format!("{}:{}\t{:?}\t{}", t.line() + 1, t.column() + 1, t.kind(), t))
+        .collect())
+}
+
+/// The syntax tree of `source`, parsed the way `Lox::eval` does.
+pub fn syntax_tree(source: &str) -> Result<String, Vec<LoxError>> {
+    let ast = parse_repl(scan(source.chars().collect())?)?;
+    Ok(ast.to_string())
+}
+
+/// Frees the objects only kept alive by reference cycles and returns how
+/// many there were.
+pub fn collect_garbage() -> usize {
+    gc::collect()
+}
+
+pub fn gc_stats() -> GcStats {
+    gc::stats()
+}
+
+/// Lets scripts evaluated on the current thread use up to `bytes` of its
+/// native stack before calls fail with a "stack overflow" runtime error.
+/// The default of 1 MiB fits any thread Rust spawns, raise it for threads
+/// spawned with more, like [`STACK_SIZE`].
+pub fn set_stack_limit(bytes: usize) {
+    stack::set_limit(bytes);
+}
@@ -0,0 +1,74 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use crate::{
+    gc,
+    interpret::{display_once, Value},
+};
+
+pub type ListRef = Rc<RefCell<List>>;
+
+pub struct List {
+    items: Vec<Value>,
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl List {
+    pub fn create(items: Vec<Value>) -> ListRef {
+        let list = Rc::new(RefCell::new(List { items }));
+        gc::track_list(&list);
+        list
+    }
+    pub fn items(&self) -> &Vec<Value> {
+        &self.items
+    }
+    pub fn items_mut(&mut self) -> &mut Vec<Value> {
+        &mut self.items
+    }
+    pub fn get(&self, index: &Value) -> Result<Value, String> {
+        let i = position(index, self.items.len())?;
+        Ok(self.items[i].clone())
+    }
+    pub fn set(&mut self, index: &Value, value: Value) -> Result<(), String> {
+        let i = position(index, self.items.len())?;
+        self.items[i] = value;
+        Ok(())
+    }
+    /// Removes all items, used to break reference cycles.
+    pub fn take_items(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.items)
+    }
+}
+
+/// Converts `index` to a position below `len`.
+pub fn position(index: &Value, len: usize) -> Result<usize, String> {
+    match index {
+        Value::Number(n) if n.fract() == 0.0 && *n >= 0.0 => {
+            if (*n as usize) < len {
+                Ok(*n as usize)
+            } else {
+                Err(format!("list index {} out of range", n))
+            }
+        }
+        Value::Number(_) => Err("list index must be a non-negative integer".to_string()),
+        _ => Err("list index must be a number".to_string()),
+    }
+}
+
+impl Display for List {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rep = display_once(self as *const List as usize, "[...]", || {
+            let items = self
+                .items
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>();
+            format!("[{}]", items.join(", "))
+        });
+        write!(f, "{}", rep)
+    }
+}
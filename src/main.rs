@@ -1,70 +1,74 @@
-mod ast;
-mod environment;
-mod function;
-mod interpret;
-mod parse;
-mod scan;
-mod token;
+mod repl;
 
-use interpret::interpret;
-use parse::{parse_expresssion, parse_source};
-use scan::scan;
+use arlox::Lox;
 use std::{
     env::args,
-    io::{stdin, Write},
+    path::{Path, PathBuf},
     process::exit,
+    thread,
 };
 
-fn lox_error(line: usize, text: &str) {
-    println!("Error [line {}]: {}\n", line + 1, text);
-}
-
-fn interpret_source(mut text: String) -> Option<interpret::Value> {
-    let tokens = scan(text.drain(..).collect::<Vec<char>>()).ok()?;
-    let ast = parse_source(tokens)?;
-    interpret(ast)
-}
-
-fn interpret_line(mut text: String) -> Option<interpret::Value> {
-    let tokens = scan(text.drain(..).collect::<Vec<char>>()).ok()?;
-    let ast = parse_expresssion(tokens)?;
-    interpret(ast)
-}
-
-fn run_file(args: Vec<String>) {
+fn run_file(args: Vec<String>, vm: bool) {
     if args.len() != 2 {
-        eprintln!("help: lox [script]");
+        eprintln!("help: lox [--vm] [script | test <dir>]");
         exit(1);
     }
     let file = &args[1];
-    let text = std::fs::read_to_string(file).expect(&format!("Error: cant open file {}", file));
-    interpret_source(text);
+    let text =
+        std::fs::read_to_string(file).unwrap_or_else(|_| panic!("Error: cant open file {}", file));
+    let mut lox = if vm { Lox::with_vm() } else { Lox::new() };
+    if let Err(errors) = lox.run_script(&text, PathBuf::from(file)) {
+        lox.report_errors(&text, &errors);
+        exit(if errors.iter().any(|e| e.is_runtime()) {
+            70
+        } else {
+            65
+        });
+    }
 }
 
-fn repl() {
-    loop {
-        print!("> ");
-        std::io::stdout().flush().expect("failed to flush stdout");
-        let mut line = String::new();
-        let rsl = stdin()
-            .read_line(&mut line)
-            .expect("failed to read from stdin");
-        if rsl == 0 {
-            break;
-        }
-        if line.trim().len() > 0 {
-            if let Some(output) = interpret_line(line) {
-                println!("{}", output);
+fn run_tests(args: Vec<String>, vm: bool) {
+    if args.len() != 3 {
+        eprintln!("help: lox [--vm] test <dir>");
+        exit(1);
+    }
+    match arlox::run_tests(Path::new(&args[2]), vm) {
+        Ok(report) => {
+            println!("{}", report);
+            if !report.ok() {
+                exit(1);
             }
         }
+        Err(err) => {
+            eprintln!("can't run tests in {}: {}", args[2], err);
+            exit(1);
+        }
     }
 }
 
-fn main() {
-    let args = args().collect::<Vec<String>>();
-    if args.len() == 1 {
-        repl();
+fn start() {
+    // half the stack, the rest is for the calls around scripts
+    arlox::set_stack_limit(arlox::STACK_SIZE / 2);
+    let mut args = args().collect::<Vec<String>>();
+    let vm = args.get(1).is_some_and(|a| a == "--vm");
+    if vm {
+        args.remove(1);
+    }
+    if args.len() == 1 && !vm {
+        repl::repl();
+    } else if args.get(1).is_some_and(|a| a == "test") {
+        run_tests(args, vm);
     } else {
-        run_file(args);
+        run_file(args, vm);
+    }
+}
+
+fn main() {
+    let lox = thread::Builder::new()
+        .stack_size(arlox::STACK_SIZE)
+        .spawn(start)
+        .expect("can't start the interpreter thread");
+    if lox.join().is_err() {
+        exit(101);
     }
 }
@@ -0,0 +1,134 @@
+use std::{cell::RefCell, cmp::Ordering, collections::BTreeMap, fmt::Display, rc::Rc};
+
+use crate::{
+    gc,
+    interpret::{display_once, Value},
+};
+
+pub type MapRef = Rc<RefCell<Map>>;
+
+/// Values that can be used as map keys. Keys are kept sorted, booleans
+/// first, then numbers, then strings, so maps always display the same way.
+#[derive(Clone)]
+pub enum Key {
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+pub struct Map {
+    entries: BTreeMap<Key, Value>,
+}
+
+impl Key {
+    pub fn from_value(value: &Value) -> Result<Key, String> {
+        match value {
+            Value::Boolean(b) => Ok(Key::Boolean(*b)),
+            // Adding zero turns -0 into 0, they are the same key.
+            Value::Number(n) => Ok(Key::Number(n + 0.0)),
+            Value::String(s) => Ok(Key::String(s.clone())),
+            _ => Err("map keys must be strings, numbers or booleans".to_string()),
+        }
+    }
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Boolean(b) => Value::Boolean(*b),
+            Key::Number(n) => Value::Number(*n),
+            Key::String(s) => Value::String(s.clone()),
+        }
+    }
+    fn rank(&self) -> u8 {
+        match self {
+            Key::Boolean(_) => 0,
+            Key::Number(_) => 1,
+            Key::String(_) => 2,
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Key::Boolean(a), Key::Boolean(b)) => a.cmp(b),
+            (Key::Number(a), Key::Number(b)) => a.total_cmp(b),
+            (Key::String(a), Key::String(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Map {
+    pub fn create() -> MapRef {
+        let map = Rc::new(RefCell::new(Map {
+            entries: BTreeMap::new(),
+        }));
+        gc::track_map(&map);
+        map
+    }
+    pub fn entries(&self) -> &BTreeMap<Key, Value> {
+        &self.entries
+    }
+    pub fn get(&self, key: &Value) -> Result<Value, String> {
+        let key = Key::from_value(key)?;
+        match self.entries.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => Err(format!("undefined key '{}'", key)),
+        }
+    }
+    pub fn set(&mut self, key: &Value, value: Value) -> Result<(), String> {
+        self.entries.insert(Key::from_value(key)?, value);
+        Ok(())
+    }
+    pub fn has(&self, key: &Value) -> Result<bool, String> {
+        Ok(self.entries.contains_key(&Key::from_value(key)?))
+    }
+    /// Removes `key` and returns its value, nil if it was missing.
+    pub fn delete(&mut self, key: &Value) -> Result<Value, String> {
+        let removed = self.entries.remove(&Key::from_value(key)?);
+        Ok(removed.unwrap_or(Value::Nil))
+    }
+    /// Removes all entries, used to break reference cycles.
+    pub fn take_entries(&mut self) -> BTreeMap<Key, Value> {
+        std::mem::take(&mut self.entries)
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rep = display_once(self as *const Map as usize, "{...}", || {
+            let entries = self
+                .entries
+                .iter()
+                .map(|(k, v)| format!("{}: {}", k, v))
+                .collect::<Vec<String>>();
+            format!("{{{}}}", entries.join(", "))
+        });
+        write!(f, "{}", rep)
+    }
+}
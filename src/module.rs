@@ -0,0 +1,134 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    ast::Ast,
+    environment::Env,
+    error::{Frame, LoxError},
+    gc,
+    interpret::Value,
+    parse::parse_source,
+    resolve::resolve,
+    scan::scan,
+    token::Token,
+};
+
+pub type ModuleRef = Rc<Module>;
+pub type LoaderRef = Rc<RefCell<Loader>>;
+
+/// The namespace of an imported file, its global environment.
+pub struct Module {
+    name: String,
+    path: PathBuf,
+    env: Env,
+}
+
+/// Modules loaded so far, shared by the main script and all the modules it
+/// imports, so every file runs once.
+#[derive(Default)]
+pub struct Loader {
+    modules: HashMap<PathBuf, ModuleRef>,
+    /// Files being imported, outermost first.
+    loading: Vec<PathBuf>,
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Module {
+    fn create(path: PathBuf, env: Env) -> ModuleRef {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let module = Rc::new(Module { name, path, env });
+        gc::track_module(&module);
+        module
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn env(&self) -> Env {
+        self.env.clone()
+    }
+    pub fn get(&self, name: &str) -> Result<Value, String> {
+        match self.env.borrow().get(&name.to_string()) {
+            Some(value) => Ok(value),
+            None => Err(format!("module '{}' has no member '{}'", self.name, name)),
+        }
+    }
+}
+
+impl Loader {
+    pub fn create() -> LoaderRef {
+        Rc::new(RefCell::new(Loader::default()))
+    }
+    /// File of the module whose global environment is `env`.
+    pub fn file_of(&self, env: &Env) -> Option<PathBuf> {
+        self.modules
+            .values()
+            .find(|m| Rc::ptr_eq(&m.env, env))
+            .map(|m| m.path.clone())
+    }
+}
+
+/// Loads the module at `path`, relative to the directory of `importer`,
+/// unless it was loaded before. `run` executes the parsed file and returns
+/// its global environment. Errors in the module are raised at `token`, the
+/// import statement, with the module's line in their trace.
+pub fn import(
+    loader: &LoaderRef,
+    importer: Option<&Path>,
+    token: &Token,
+    path: &str,
+    run: impl FnOnce(&Ast, PathBuf) -> Result<Env, LoxError>,
+) -> Result<ModuleRef, LoxError> {
+    let fail = |message: String| LoxError::runtime(token, &message);
+    let dir = importer.and_then(|i| i.parent()).unwrap_or(Path::new(""));
+    let path = dir
+        .join(path)
+        .canonicalize()
+        .map_err(|err| fail(format!("can't open module '{}': {}", path, err)))?;
+    if let Some(module) = loader.borrow().modules.get(&path) {
+        return Ok(module.clone());
+    }
+    let cycle = loader.borrow().loading.iter().position(|p| *p == path);
+    if let Some(start) = cycle {
+        let files = loader.borrow().loading[start..]
+            .iter()
+            .chain([&path])
+            .map(|p| p.display().to_string())
+            .collect::<Vec<String>>();
+        return Err(fail(format!("import cycle: {}", files.join(" -> "))));
+    }
+
+    let text = std::fs::read_to_string(&path)
+        .map_err(|err| fail(format!("can't open module '{}': {}", path.display(), err)))?;
+    let file = path.display().to_string();
+    let ast = parse_module(&text).map_err(|errors| {
+        let messages = errors.iter().map(|e| e.message().clone());
+        let trace = errors.iter().map(|e| Frame::module(file.clone(), e.line()));
+        fail(messages.collect::<Vec<String>>().join("; ")).with_trace(trace.collect())
+    })?;
+    loader.borrow_mut().loading.push(path.clone());
+    let rsl = run(&ast, path.clone());
+    loader.borrow_mut().loading.pop();
+    let env = rsl.map_err(|err| err.raised_at(token, Frame::module(file, err.line())))?;
+
+    let module = Module::create(path.clone(), env);
+    loader.borrow_mut().modules.insert(path, module.clone());
+    Ok(module)
+}
+
+fn parse_module(text: &str) -> Result<Ast, Vec<LoxError>> {
+    let ast = parse_source(scan(text.chars().collect())?)?;
+    resolve(&ast)?;
+    Ok(ast)
+}
@@ -0,0 +1,39 @@
+use std::{
+    cell::RefCell,
+    io::{stderr, stdout, Write},
+    rc::Rc,
+};
+
+pub type OutputRef = Rc<RefCell<Output>>;
+
+/// Where scripts and their errors are written, stdout and stderr unless
+/// the host redirects them. Shared by the main script and the modules it
+/// imports.
+pub struct Output {
+    out: Box<dyn Write>,
+    err: Box<dyn Write>,
+}
+
+impl Output {
+    pub fn create() -> OutputRef {
+        Rc::new(RefCell::new(Output {
+            out: Box::new(stdout()),
+            err: Box::new(stderr()),
+        }))
+    }
+    pub fn set_out(&mut self, out: Box<dyn Write>) {
+        self.out = out;
+    }
+    pub fn set_err(&mut self, err: Box<dyn Write>) {
+        self.err = err;
+    }
+    /// Writes a line of script output.
+    pub fn print(&mut self, text: &str) -> Result<(), String> {
+        writeln!(self.out, "{}", text).map_err(|err| format!("can't write output: {}", err))
+    }
+    /// Writes a line to the error sink, where failing to write is not
+    /// worth reporting.
+    pub fn error(&mut self, text: &str) {
+        let _ = writeln!(self.err, "{}", text);
+    }
+}
@@ -1,35 +1,19 @@
 use crate::{
     ast::{
-        AssignExpr, Ast, AstNodeKind, AstNodeRef, BinaryExpr, Block, BreakStmt, ExprStmt, FunCall,
-        FunDecl, FunDef, GroupExpr, IfStmt, LiteralExpr, PrintStmt, Program, ReturnStmt, UnaryExpr,
-        VarDecl, WhileStmt,
+        AssignExpr, Ast, AstNodeKind, AstNodeRef, BinaryExpr, Block, BreakStmt, ClassDecl,
+        CoalesceExpr, ConditionalExpr, ContinueStmt, ExprStmt, ForStmt, FunCall, FunDecl, FunDef,
+        GetExpr, GroupExpr, IfStmt, ImportStmt, IndexExpr, IndexSetExpr, InterpolationExpr,
+        ListExpr, LiteralExpr, MapExpr, PrintStmt, Program, ReturnStmt, SetExpr, SuperExpr,
+        ThrowStmt, TryStmt, UnaryExpr, VarDecl, WhileStmt,
     },
-    lox_error,
+    error::LoxError,
     token::{Token, TokenKind},
 };
 
 struct Parser {
     current: usize,
     tokens: Vec<Token>,
-}
-
-fn desugar_for(
-    initialize: Option<AstNodeRef>,
-    condition: Option<AstNodeRef>,
-    increment: Option<AstNodeRef>,
-    stmt: AstNodeRef,
-) -> AstNodeRef {
-    let mut stmt = stmt;
-    let condition = condition
-        .unwrap_or_else(|| LiteralExpr::create(Token::new(TokenKind::True, "true".to_string(), 0)));
-    if let Some(inc) = increment {
-        stmt = Block::create(vec![stmt, inc]);
-    }
-    let mut whloop = WhileStmt::create(condition, stmt);
-    if let Some(init) = initialize {
-        whloop = Block::create(vec![init, whloop]);
-    }
-    whloop
+    errors: Vec<LoxError>,
 }
 
 impl Parser {
@@ -37,47 +21,138 @@ impl Parser {
         Parser {
             current: 0,
             tokens: vec![],
+            errors: vec![],
         }
     }
-    fn parse(&mut self, tokens: Vec<Token>, expr: bool) -> Option<Ast> {
+    fn parse(&mut self, tokens: Vec<Token>, expr: bool) -> Result<Ast, Vec<LoxError>> {
         self.tokens = tokens;
-        let rsl = if expr {
+        let mut rsl = if expr {
             self.parse_expression()
         } else {
             self.parse_program()
         };
-        if expr && !self.is_at_end() {
-            lox_error(0, "unexpected end of expression")
+        if rsl.is_ok() && expr && !self.is_at_end() {
+            rsl = Err(LoxError::parse(
+                &self.peek(),
+                "unexpected end of expression",
+            ));
         }
         self.current = 0;
         self.tokens.clear();
         match rsl {
-            Ok(node) => Some(Ast::create(node)),
-            Err(_) => None,
+            Ok(node) if self.errors.is_empty() => Ok(Ast::create(node)),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(err) => {
+                self.errors.push(err);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+    /// Parses a bare expression when the whole input is one, with or
+    /// without a trailing ';', and a full program otherwise.
+    fn parse_repl(&mut self, tokens: Vec<Token>) -> Result<Ast, Vec<LoxError>> {
+        let mut expr_tokens = tokens.clone();
+        let len = expr_tokens.len();
+        if len > 2 && expr_tokens[len - 2].kind() == TokenKind::Semicolon {
+            expr_tokens.remove(len - 2);
+        }
+        match self.parse(expr_tokens, true) {
+            Ok(ast) => Ok(ast),
+            Err(_) => self.parse(tokens, false),
         }
     }
-    fn parse_program(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_program(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut decs = vec![];
         while !self.check(TokenKind::EOF) {
-            let stmt = self.parse_declaration()?;
-            decs.push(stmt);
+            if let Some(stmt) = self.parse_recovering() {
+                decs.push(stmt);
+            } else if self.check(TokenKind::RightBrace) {
+                self.advance();
+            }
         }
         Ok(Program::create(decs))
     }
-    fn parse_declaration(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_recovering(&mut self) -> Option<AstNodeRef> {
+        match self.parse_declaration() {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                None
+            }
+        }
+    }
+    fn synchronize(&mut self) {
+        // closing braces are left for the enclosing block to consume
+        while !self.is_at_end() && !self.check(TokenKind::RightBrace) {
+            if self.advance().kind() == TokenKind::Semicolon {
+                return;
+            }
+            match self.peek().kind() {
+                TokenKind::Class
+                | TokenKind::Fun
+                | TokenKind::Var
+                | TokenKind::For
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::Print
+                | TokenKind::Return
+                | TokenKind::Throw
+                | TokenKind::Import
+                | TokenKind::Try => return,
+                _ => {}
+            }
+        }
+    }
+    fn parse_declaration(&mut self) -> Result<AstNodeRef, LoxError> {
         if self.check(TokenKind::Var) {
             return self.parse_var_decl();
         } else if self.check(TokenKind::Fun) {
             return self.parse_func_decl();
+        } else if self.check(TokenKind::Class) {
+            return self.parse_class_decl();
+        } else if self.check(TokenKind::Import) {
+            return self.parse_import_decl();
         }
-        return self.parse_stmt();
+        self.parse_stmt()
     }
-    fn parse_func_decl(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_func_decl(&mut self) -> Result<AstNodeRef, LoxError> {
         self.advance();
         let name = self.consume(TokenKind::Identifier)?;
-        Ok(self.parse_func(Some(name))?)
+        self.parse_func(Some(name))
+    }
+    fn parse_class_decl(&mut self) -> Result<AstNodeRef, LoxError> {
+        self.advance();
+        let name = self.consume(TokenKind::Identifier)?;
+        let mut superclass = None;
+        if self.match_kinds(&[TokenKind::Less]) {
+            let parent = self.consume(TokenKind::Identifier)?;
+            if parent.text() == name.text() {
+                return Err(LoxError::parse(
+                    &parent,
+                    "a class can't inherit from itself",
+                ));
+            }
+            superclass = Some(LiteralExpr::create(parent));
+        }
+        self.consume(TokenKind::LeftBrace)?;
+        let mut methods = vec![];
+        while !self.check(TokenKind::RightBrace) && !self.is_at_end() {
+            let name = self.consume(TokenKind::Identifier)?;
+            let (params, block) = self.parse_func_body()?;
+            methods.push(FunDecl::create_method(name, params, block));
+        }
+        self.consume(TokenKind::RightBrace)?;
+        Ok(ClassDecl::create(name, superclass, methods))
+    }
+    fn parse_func(&mut self, name: Option<Token>) -> Result<AstNodeRef, LoxError> {
+        let (params, block) = self.parse_func_body()?;
+        match name {
+            Some(name) => Ok(FunDecl::create(name, params, block)),
+            None => Ok(FunDef::create(params, block)),
+        }
     }
-    fn parse_func(&mut self, name: Option<Token>) -> Result<AstNodeRef, ()> {
+    fn parse_func_body(&mut self) -> Result<(Vec<Token>, AstNodeRef), LoxError> {
         self.consume(TokenKind::LeftParen)?;
 
         let mut params = vec![];
@@ -85,11 +160,10 @@ impl Parser {
             params.push(self.consume(TokenKind::Identifier)?);
             while self.check(TokenKind::Comma) {
                 if params.len() == 255 {
-                    lox_error(
-                        self.peek().line(),
+                    return Err(LoxError::parse(
+                        &self.peek(),
                         "functions are not allowed to have more than 255 params",
-                    );
-                    return Err(());
+                    ));
                 }
                 self.advance();
                 params.push(self.consume(TokenKind::Identifier)?)
@@ -98,12 +172,17 @@ impl Parser {
         self.consume(TokenKind::RightParen)?;
 
         let block = self.parse_block()?;
-        match name {
-            Some(name) => Ok(FunDecl::create(name, params, block)),
-            None => Ok(FunDef::create(params, block)),
-        }
+        Ok((params, block))
     }
-    fn parse_var_decl(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_import_decl(&mut self) -> Result<AstNodeRef, LoxError> {
+        self.advance();
+        let path = self.consume(TokenKind::String)?;
+        self.consume(TokenKind::As)?;
+        let name = self.consume(TokenKind::Identifier)?;
+        self.consume(TokenKind::Semicolon)?;
+        Ok(ImportStmt::create(path, name))
+    }
+    fn parse_var_decl(&mut self) -> Result<AstNodeRef, LoxError> {
         self.advance();
         let id = self.consume(TokenKind::Identifier)?;
         let mut expr = None;
@@ -114,14 +193,23 @@ impl Parser {
         self.consume(TokenKind::Semicolon)?;
         Ok(VarDecl::create(id, expr))
     }
-    fn parse_stmt(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_stmt(&mut self) -> Result<AstNodeRef, LoxError> {
         let node;
         if self.check(TokenKind::If) {
             node = self.parse_if_stmt();
         } else if self.check(TokenKind::While) {
-            node = self.parse_while_stmt();
+            node = self.parse_while_stmt(None);
+        } else if self.check(TokenKind::Identifier) && self.peek_next() == TokenKind::Colon {
+            node = self.parse_labeled_stmt();
         } else if self.check(TokenKind::Break) {
-            node = Ok(BreakStmt::create(self.advance()));
+            let tkn = self.advance();
+            let label = self.parse_label();
+            node = Ok(BreakStmt::create(tkn, label));
+            self.consume(TokenKind::Semicolon)?;
+        } else if self.check(TokenKind::Continue) {
+            let tkn = self.advance();
+            let label = self.parse_label();
+            node = Ok(ContinueStmt::create(tkn, label));
             self.consume(TokenKind::Semicolon)?;
         } else if self.check(TokenKind::Return) {
             let tkn = self.advance();
@@ -132,11 +220,18 @@ impl Parser {
             };
             self.consume(TokenKind::Semicolon)?;
             node = Ok(ReturnStmt::create(tkn, expr));
+        } else if self.check(TokenKind::Throw) {
+            let tkn = self.advance();
+            let expr = self.parse_expression()?;
+            self.consume(TokenKind::Semicolon)?;
+            node = Ok(ThrowStmt::create(tkn, expr));
+        } else if self.check(TokenKind::Try) {
+            node = self.parse_try_stmt();
         } else if self.check(TokenKind::For) {
-            node = self.parse_for_stmt();
+            node = self.parse_for_stmt(None);
         } else if self.check(TokenKind::Print) {
-            self.advance();
-            node = Ok(PrintStmt::create(self.parse_expression()?));
+            let tkn = self.advance();
+            node = Ok(PrintStmt::create(tkn, self.parse_expression()?));
             self.consume(TokenKind::Semicolon)?;
         } else if self.check(TokenKind::LeftBrace) {
             node = self.parse_block();
@@ -146,7 +241,7 @@ impl Parser {
         }
         node
     }
-    fn parse_if_stmt(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_if_stmt(&mut self) -> Result<AstNodeRef, LoxError> {
         self.advance();
         self.consume(TokenKind::LeftParen)?;
         let expr = self.parse_expression()?;
@@ -159,16 +254,57 @@ impl Parser {
         }
         Ok(IfStmt::create(expr, stmt, elstmt))
     }
-    fn parse_while_stmt(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_try_stmt(&mut self) -> Result<AstNodeRef, LoxError> {
+        let tkn = self.advance();
+        let body = self.parse_block()?;
+        let mut catch = None;
+        if self.match_kinds(&[TokenKind::Catch]) {
+            self.consume(TokenKind::LeftParen)?;
+            let param = self.consume(TokenKind::Identifier)?;
+            self.consume(TokenKind::RightParen)?;
+            catch = Some((param, self.parse_block()?));
+        }
+        let mut finally = None;
+        if self.match_kinds(&[TokenKind::Finally]) {
+            finally = Some(self.parse_block()?);
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(LoxError::parse(
+                &self.peek(),
+                "expected 'catch' or 'finally' after try block",
+            ));
+        }
+        Ok(TryStmt::create(tkn, body, catch, finally))
+    }
+    /// `label: while (...)` or `label: for (...)`.
+    fn parse_labeled_stmt(&mut self) -> Result<AstNodeRef, LoxError> {
+        let label = self.advance();
+        self.advance();
+        if self.check(TokenKind::While) {
+            self.parse_while_stmt(Some(label))
+        } else if self.check(TokenKind::For) {
+            self.parse_for_stmt(Some(label))
+        } else {
+            Err(LoxError::parse(&label, "only loops can be labeled"))
+        }
+    }
+    /// The optional label after `break` or `continue`.
+    fn parse_label(&mut self) -> Option<Token> {
+        match self.check(TokenKind::Identifier) {
+            true => Some(self.advance()),
+            false => None,
+        }
+    }
+    fn parse_while_stmt(&mut self, label: Option<Token>) -> Result<AstNodeRef, LoxError> {
         self.advance();
         self.consume(TokenKind::LeftParen)?;
         let expr = self.parse_expression()?;
         self.consume(TokenKind::RightParen)?;
         let stmt = self.parse_stmt()?;
-        Ok(WhileStmt::create(expr, stmt))
+        Ok(WhileStmt::create(label, expr, stmt))
     }
-    fn parse_for_stmt(&mut self) -> Result<AstNodeRef, ()> {
-        self.advance();
+    fn parse_for_stmt(&mut self, label: Option<Token>) -> Result<AstNodeRef, LoxError> {
+        let keyword = self.advance();
         self.consume(TokenKind::LeftParen)?;
 
         let initialize;
@@ -178,7 +314,7 @@ impl Parser {
         } else if self.check(TokenKind::Var) {
             initialize = Some(self.parse_var_decl()?);
         } else {
-            initialize = Some(self.parse_expression()?);
+            initialize = Some(ExprStmt::create(self.parse_expression()?));
             self.consume(TokenKind::Semicolon)?;
         }
 
@@ -196,52 +332,78 @@ impl Parser {
         self.consume(TokenKind::RightParen)?;
         let stmt = self.parse_stmt()?;
 
-        Ok(desugar_for(initialize, condition, increment, stmt))
+        Ok(ForStmt::create(
+            label, keyword, initialize, condition, increment, stmt,
+        ))
     }
-    fn parse_block(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_block(&mut self) -> Result<AstNodeRef, LoxError> {
         self.advance();
         let mut decs = vec![];
-        while !self.check(TokenKind::RightBrace) {
-            decs.push(self.parse_declaration()?)
+        while !self.check(TokenKind::RightBrace) && !self.is_at_end() {
+            if let Some(stmt) = self.parse_recovering() {
+                decs.push(stmt);
+            }
         }
-        self.advance();
+        self.consume(TokenKind::RightBrace)?;
         Ok(Block::create(decs))
     }
-    fn parse_expression(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_expression(&mut self) -> Result<AstNodeRef, LoxError> {
         self.parse_assignment()
     }
-    fn parse_assignment(&mut self) -> Result<AstNodeRef, ()> {
-        let mut nodes = vec![self.parse_logic_or()?];
-        let mut lines = vec![self.peek().line()];
+    fn parse_assignment(&mut self) -> Result<AstNodeRef, LoxError> {
+        let mut nodes = vec![self.parse_conditional()?];
+        let mut equals = vec![self.peek()];
         while self.check(TokenKind::Equal) {
             let tkn = self.advance();
-            lines.push(tkn.line());
-            nodes.push(self.parse_logic_or()?);
-        }
-        let mut expr = nodes.pop().ok_or(())?;
-        lines.pop();
-        while nodes.len() > 0 {
-            let node = nodes.pop().ok_or(())?;
-            let line = lines.pop().ok_or(())?;
+            equals.push(tkn);
+            nodes.push(self.parse_conditional()?);
+        }
+        let mut expr = nodes.pop().expect("assignment without expression");
+        equals.pop();
+        while let Some(node) = nodes.pop() {
+            let equal = equals.pop().expect("assignment without '='");
             match node.kind() {
                 AstNodeKind::LiteralExpr(tkn) => {
                     expr = match tkn.kind() {
                         TokenKind::Identifier => AssignExpr::create(tkn.clone(), expr),
                         _ => {
-                            lox_error(line, "invalid l-value");
-                            return Err(());
+                            return Err(LoxError::parse(&equal, "invalid l-value"));
                         }
                     }
                 }
+                AstNodeKind::GetExpr(object, name) => expr = SetExpr::create(object, name, expr),
+                AstNodeKind::IndexExpr(object, bracket, index) => {
+                    expr = IndexSetExpr::create(object, bracket, index, expr)
+                }
                 _ => {
-                    lox_error(line, "invalid l-value");
-                    return Err(());
+                    return Err(LoxError::parse(&equal, "invalid l-value"));
                 }
             }
         }
         Ok(expr)
     }
-    fn parse_logic_or(&mut self) -> Result<AstNodeRef, ()> {
+    /// Right associative, `a ? b : c ? d : e` nests in the last branch.
+    fn parse_conditional(&mut self) -> Result<AstNodeRef, LoxError> {
+        let condition = self.parse_coalesce()?;
+        if !self.check(TokenKind::Question) {
+            return Ok(condition);
+        }
+        let tkn = self.advance();
+        let then = self.parse_expression()?;
+        self.consume(TokenKind::Colon)?;
+        let otherwise = self.parse_conditional()?;
+        Ok(ConditionalExpr::create(tkn, condition, then, otherwise))
+    }
+    fn parse_coalesce(&mut self) -> Result<AstNodeRef, LoxError> {
+        let mut lexpr = self.parse_logic_or()?;
+        while self.check(TokenKind::QuestionQuestion) {
+            let opr = self.advance();
+            let rexpr = self.parse_logic_or()?;
+            lexpr = CoalesceExpr::create(opr, lexpr, rexpr);
+        }
+        Ok(lexpr)
+    }
+    fn parse_logic_or(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut lexpr = self.parse_logic_and()?;
         while self.check(TokenKind::Or) {
             let opr = self.advance();
@@ -250,7 +412,7 @@ impl Parser {
         }
         Ok(lexpr)
     }
-    fn parse_logic_and(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_logic_and(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut lexpr = self.parse_equality()?;
         while self.check(TokenKind::And) {
             let opr = self.advance();
@@ -259,7 +421,7 @@ impl Parser {
         }
         Ok(lexpr)
     }
-    fn parse_equality(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_equality(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut expr = self.parse_comparison()?;
         while self.match_kinds(&[TokenKind::EqualEqual, TokenKind::BangEqual]) {
             let operator = self.previous();
@@ -267,7 +429,7 @@ impl Parser {
         }
         Ok(expr)
     }
-    fn parse_comparison(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_comparison(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut expr = self.parse_term()?;
         while self.match_kinds(&[
             TokenKind::GreaterEqual,
@@ -280,7 +442,7 @@ impl Parser {
         }
         Ok(expr)
     }
-    fn parse_term(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_term(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut expr = self.parse_factor()?;
         while self.match_kinds(&[TokenKind::Plus, TokenKind::Minus]) {
             let operator = self.previous();
@@ -288,7 +450,7 @@ impl Parser {
         }
         Ok(expr)
     }
-    fn parse_factor(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_factor(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut expr = self.parse_unary()?;
         while self.match_kinds(&[TokenKind::Slash, TokenKind::Star]) {
             let operator = self.previous();
@@ -296,7 +458,7 @@ impl Parser {
         }
         Ok(expr)
     }
-    fn parse_unary(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_unary(&mut self) -> Result<AstNodeRef, LoxError> {
         let node = if self.match_kinds(&[TokenKind::Minus, TokenKind::Bang]) {
             let operator = self.previous();
             UnaryExpr::create(operator, self.parse_unary()?)
@@ -305,34 +467,48 @@ impl Parser {
         };
         Ok(node)
     }
-    fn parse_call(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_call(&mut self) -> Result<AstNodeRef, LoxError> {
         let mut expr = self.parse_primary()?;
-        while self.check(TokenKind::LeftParen) {
-            let tkn = self.consume(TokenKind::LeftParen)?;
-            let args = if !self.check(TokenKind::RightParen) {
-                self.parse_arguments()?
+        loop {
+            if self.check(TokenKind::LeftParen) {
+                let tkn = self.consume(TokenKind::LeftParen)?;
+                let args = if !self.check(TokenKind::RightParen) {
+                    self.parse_arguments()?
+                } else {
+                    vec![]
+                };
+                self.consume(TokenKind::RightParen)?;
+                expr = FunCall::create(expr, args, tkn);
+            } else if self.match_kinds(&[TokenKind::Dot]) {
+                let name = self.consume(TokenKind::Identifier)?;
+                expr = GetExpr::create(expr, name);
+            } else if self.match_kinds(&[TokenKind::LeftBracket]) {
+                let bracket = self.previous();
+                let index = self.parse_expression()?;
+                self.consume(TokenKind::RightBracket)?;
+                expr = IndexExpr::create(expr, bracket, index);
             } else {
-                vec![]
-            };
-            self.consume(TokenKind::RightParen)?;
-            expr = FunCall::create(expr, args, tkn.line());
+                break;
+            }
         }
         Ok(expr)
     }
-    fn parse_arguments(&mut self) -> Result<Vec<AstNodeRef>, ()> {
+    fn parse_arguments(&mut self) -> Result<Vec<AstNodeRef>, LoxError> {
         let mut args = vec![];
         args.push(self.parse_expression()?);
         while self.check(TokenKind::Comma) {
             if args.len() == 255 {
-                lox_error(self.peek().line(), "more than 255 arguments is not allowed");
-                return Err(());
+                return Err(LoxError::parse(
+                    &self.peek(),
+                    "more than 255 arguments is not allowed",
+                ));
             }
             self.advance();
             args.push(self.parse_expression()?);
         }
         Ok(args)
     }
-    fn parse_primary(&mut self) -> Result<AstNodeRef, ()> {
+    fn parse_primary(&mut self) -> Result<AstNodeRef, LoxError> {
         if self.match_kinds(&[
             TokenKind::True,
             TokenKind::False,
@@ -340,6 +516,7 @@ impl Parser {
             TokenKind::String,
             TokenKind::Identifier,
             TokenKind::Nil,
+            TokenKind::This,
         ]) {
             Ok(LiteralExpr::create(self.previous()))
         } else if self.match_kinds(&[TokenKind::LeftParen]) {
@@ -348,17 +525,76 @@ impl Parser {
             Ok(GroupExpr::create(expr))
         } else if self.match_kinds(&[TokenKind::Fun]) {
             self.parse_func(None)
+        } else if self.match_kinds(&[TokenKind::Super]) {
+            let keyword = self.previous();
+            self.consume(TokenKind::Dot)?;
+            let method = self.consume(TokenKind::Identifier)?;
+            Ok(SuperExpr::create(keyword, method))
+        } else if self.match_kinds(&[TokenKind::LeftBracket]) {
+            let bracket = self.previous();
+            let items = if !self.check(TokenKind::RightBracket) {
+                self.parse_arguments()?
+            } else {
+                vec![]
+            };
+            self.consume(TokenKind::RightBracket)?;
+            Ok(ListExpr::create(bracket, items))
+        } else if self.match_kinds(&[TokenKind::LeftBrace]) {
+            // Blocks are only parsed as statements, so a brace starting an
+            // expression opens a map.
+            self.parse_map()
+        } else if self.check(TokenKind::Interpolation) {
+            self.parse_interpolation()
         } else {
-            super::lox_error(self.peek().line(), &format!("expression expected"));
-            Err(())
+            Err(LoxError::parse(&self.peek(), "expression expected"))
+        }
+    }
+    /// The scanner splits an interpolated string around its expressions,
+    /// a string literal token ends it.
+    fn parse_interpolation(&mut self) -> Result<AstNodeRef, LoxError> {
+        let unquote = |t: &Token| t.text()[1..t.text().len() - 1].to_string();
+        let token = self.advance();
+        let mut strings = vec![unquote(&token)];
+        let mut exprs = vec![];
+        loop {
+            exprs.push(self.parse_expression()?);
+            let next = self.peek();
+            if !matches!(next.kind(), TokenKind::Interpolation | TokenKind::String) {
+                return Err(LoxError::parse(
+                    &next,
+                    "expected '}' after interpolated expression",
+                ));
+            }
+            self.advance();
+            strings.push(unquote(&next));
+            if next.kind() == TokenKind::String {
+                break;
+            }
         }
+        Ok(InterpolationExpr::create(token, strings, exprs))
+    }
+    fn parse_map(&mut self) -> Result<AstNodeRef, LoxError> {
+        let brace = self.previous();
+        let mut entries = vec![];
+        if !self.check(TokenKind::RightBrace) {
+            loop {
+                let key = self.parse_expression()?;
+                self.consume(TokenKind::Colon)?;
+                let value = self.parse_expression()?;
+                entries.push((key, value));
+                if !self.match_kinds(&[TokenKind::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenKind::RightBrace)?;
+        Ok(MapExpr::create(brace, entries))
     }
-    fn consume(&mut self, kind: TokenKind) -> Result<Token, ()> {
+    fn consume(&mut self, kind: TokenKind) -> Result<Token, LoxError> {
         if self.check(kind) {
             Ok(self.advance())
         } else {
-            super::lox_error(self.peek().line(), &format!("expedted {}", kind));
-            Err(())
+            Err(LoxError::parse(&self.peek(), &format!("expedted {}", kind)))
         }
     }
 
@@ -389,17 +625,22 @@ impl Parser {
     fn peek(&mut self) -> Token {
         self.tokens[self.current].clone()
     }
+    fn peek_next(&mut self) -> TokenKind {
+        self.tokens
+            .get(self.current + 1)
+            .map_or(TokenKind::EOF, |t| t.kind())
+    }
     fn previous(&mut self) -> Token {
         self.tokens[self.current - 1].clone()
     }
 }
 
-pub fn parse_expresssion(tokens: Vec<Token>) -> Option<Ast> {
+pub fn parse_repl(tokens: Vec<Token>) -> Result<Ast, Vec<LoxError>> {
     let mut parser = Parser::new();
-    parser.parse(tokens, true)
+    parser.parse_repl(tokens)
 }
 
-pub fn parse_source(tokens: Vec<Token>) -> Option<Ast> {
+pub fn parse_source(tokens: Vec<Token>) -> Result<Ast, Vec<LoxError>> {
     let mut parser = Parser::new();
     parser.parse(tokens, false)
 }
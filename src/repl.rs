@@ -0,0 +1,98 @@
+use std::io::{stdin, Write};
+
+use arlox::{collect_garbage, gc_stats, is_incomplete, syntax_tree, tokens, Lox};
+
+const HELP: &str = "\
+:load <file>     run a script in the current session
+:tokens <input>  show the tokens of input
+:ast <input>     show the syntax tree of input
+:env             list global bindings
+:gc              collect garbage and show heap statistics
+:reset           clear all state
+:quit            exit the repl";
+
+/// Runs a `:command`, returns false when the session should end.
+fn meta_command(lox: &mut Lox, input: &str) -> bool {
+    let input = input.trim();
+    let (command, arg) = match input.find(char::is_whitespace) {
+        Some(i) => (&input[..i], input[i..].trim()),
+        None => (input, ""),
+    };
+    match command {
+        ":quit" => return false,
+        ":reset" => *lox = Lox::new(),
+        ":env" => {
+            for (name, value) in lox.globals() {
+                println!("{} = {}", name, value);
+            }
+        }
+        ":gc" => {
+            let freed = collect_garbage();
+            let stats = gc_stats();
+            println!(
+                "freed {} objects, {} alive, {} freed in {} collections",
+                freed, stats.live, stats.freed, stats.collections
+            );
+        }
+        ":load" => match std::fs::read_to_string(arg) {
+            Ok(text) => {
+                if let Err(errors) = lox.run_script(&text, arg.into()) {
+                    lox.report_errors(&text, &errors);
+                }
+            }
+            Err(err) => eprintln!("can't open file {}: {}", arg, err),
+        },
+        ":tokens" => match tokens(arg) {
+            Ok(tokens) => {
+                for t in tokens {
+                    println!("{}", t);
+                }
+            }
+            Err(errors) => lox.report_errors(arg, &errors),
+        },
+        ":ast" => match syntax_tree(arg) {
+            Ok(ast) => println!("{}", ast),
+            Err(errors) => lox.report_errors(arg, &errors),
+        },
+        ":help" => println!("{}", HELP),
+        _ => eprintln!("unknown command {}, try :help", command),
+    }
+    true
+}
+
+/// Reads lines until they form a complete input, returns `None` on EOF.
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    let mut prompt = "> ";
+    loop {
+        print!("{}", prompt);
+        std::io::stdout().flush().expect("failed to flush stdout");
+        let rsl = stdin()
+            .read_line(&mut input)
+            .expect("failed to read from stdin");
+        if rsl == 0 {
+            return if input.is_empty() { None } else { Some(input) };
+        }
+        if input.trim_start().starts_with(':') || !is_incomplete(&input) {
+            return Some(input);
+        }
+        prompt = "... ";
+    }
+}
+
+pub fn repl() {
+    let mut lox = Lox::new();
+    while let Some(input) = read_input() {
+        if input.trim_start().starts_with(':') {
+            if !meta_command(&mut lox, &input) {
+                break;
+            }
+        } else if !input.trim().is_empty() {
+            match lox.eval(&input) {
+                Ok(Some(output)) => println!("{}", output),
+                Ok(None) => {}
+                Err(errors) => lox.report_errors(&input, &errors),
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        AssignExpr, Ast, AstNodeRef, BinaryExpr, Block, BreakStmt, ClassDecl, CoalesceExpr,
+        ConditionalExpr, ContinueStmt, ExprStmt, ForStmt, FunCall, FunDecl, FunDef, GetExpr,
+        GroupExpr, IfStmt, ImportStmt, IndexExpr, IndexSetExpr, InterpolationExpr, ListExpr,
+        LiteralExpr, MapExpr, PrintStmt, Program, ReturnStmt, SetExpr, SuperExpr, ThrowStmt,
+        TryStmt, UnaryExpr, VarDecl, WhileStmt,
+    },
+    error::LoxError,
+    token::{Token, TokenKind},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+pub struct Resolver {
+    scopes: Vec<HashMap<String, bool>>,
+    /// Names of each scope in `scopes` that are referred to from a nested
+    /// function.
+    captured: Vec<Vec<String>>,
+    /// Index in `scopes` of the outermost scope of the current function.
+    function_scope: usize,
+    /// Labels of the loops around the current statement in the current
+    /// function, innermost last.
+    loops: Vec<Option<String>>,
+    function: FunctionType,
+    class: ClassType,
+    errors: Vec<LoxError>,
+}
+
+pub fn resolve(ast: &Ast) -> Result<(), Vec<LoxError>> {
+    let mut resolver = Resolver::new();
+    ast.root().resolve(&mut resolver);
+    if resolver.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+impl Resolver {
+    fn new() -> Resolver {
+        Resolver {
+            scopes: vec![],
+            captured: vec![],
+            function_scope: 0,
+            loops: vec![],
+            function: FunctionType::None,
+            class: ClassType::None,
+            errors: vec![],
+        }
+    }
+    fn error(&mut self, token: &Token, text: &str) {
+        self.errors.push(LoxError::resolve(token, text));
+    }
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.captured.push(vec![]);
+    }
+    /// Returns the names of the closed scope that closures refer to.
+    fn end_scope(&mut self) -> Vec<String> {
+        self.scopes.pop();
+        self.captured.pop().unwrap_or_default()
+    }
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if scope.contains_key(name.text()) {
+                self.error(
+                    name,
+                    format!("variable '{}' already declared in this scope", name.text()).as_str(),
+                );
+                return;
+            }
+            scope.insert(name.text().clone(), false);
+        }
+    }
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.text().clone(), true);
+        }
+    }
+    fn define_name(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+    fn local_depth(&mut self, name: &String) -> Option<usize> {
+        let depth = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name))?;
+        let index = self.scopes.len() - 1 - depth;
+        if index < self.function_scope && !self.captured[index].contains(name) {
+            self.captured[index].push(name.clone());
+        }
+        Some(depth)
+    }
+    fn resolve_all(&mut self, nodes: &[AstNodeRef]) {
+        for n in nodes {
+            n.resolve(self);
+        }
+    }
+    /// Returns the parameters that closures refer to.
+    fn resolve_function(
+        &mut self,
+        params: &[Token],
+        block: &AstNodeRef,
+        kind: FunctionType,
+    ) -> Vec<String> {
+        let enclosing = self.function;
+        let enclosing_scope = self.function_scope;
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        self.function = kind;
+        self.function_scope = self.scopes.len();
+        self.begin_scope();
+        for p in params {
+            self.declare(p);
+            self.define(p);
+        }
+        block.resolve(self);
+        let captured = self.end_scope();
+        self.function = enclosing;
+        self.function_scope = enclosing_scope;
+        self.loops = enclosing_loops;
+        captured
+    }
+    pub fn resolve_literal(&mut self, node: &LiteralExpr) {
+        match node.token().kind() {
+            TokenKind::Identifier => {
+                if let Some(false) = self.scopes.last().and_then(|s| s.get(node.token().text())) {
+                    self.error(
+                        node.token(),
+                        "can't read local variable in its own initializer",
+                    );
+                }
+            }
+            TokenKind::This => {
+                if self.class == ClassType::None {
+                    self.error(node.token(), "'this' used outside of a class");
+                    return;
+                }
+            }
+            _ => return,
+        }
+        if let Some(depth) = self.local_depth(node.token().text()) {
+            node.set_depth(depth);
+        }
+    }
+    pub fn resolve_group(&mut self, node: &GroupExpr) {
+        node.expr().resolve(self);
+    }
+    pub fn resolve_assignment(&mut self, node: &AssignExpr) {
+        node.expr().resolve(self);
+        if let Some(depth) = self.local_depth(node.variable().text()) {
+            node.set_depth(depth);
+        }
+    }
+    pub fn resolve_conditional(&mut self, node: &ConditionalExpr) {
+        node.condition().resolve(self);
+        node.then().resolve(self);
+        node.otherwise().resolve(self);
+    }
+    pub fn resolve_coalesce(&mut self, node: &CoalesceExpr) {
+        node.lexpr().resolve(self);
+        node.rexpr().resolve(self);
+    }
+    pub fn resolve_unary(&mut self, node: &UnaryExpr) {
+        node.expr().resolve(self);
+    }
+    pub fn resolve_binary(&mut self, node: &BinaryExpr) {
+        node.lexpr().resolve(self);
+        node.rexpr().resolve(self);
+    }
+    pub fn resolve_if_stmt(&mut self, node: &IfStmt) {
+        node.expr().resolve(self);
+        node.stmt().resolve(self);
+        if let Some(elstmt) = node.elstmt() {
+            elstmt.resolve(self);
+        }
+    }
+    pub fn resolve_while_stmt(&mut self, node: &WhileStmt) {
+        node.expr().resolve(self);
+        self.resolve_loop_body(node.label(), node.stmt());
+    }
+    pub fn resolve_for_stmt(&mut self, node: &ForStmt) {
+        self.begin_scope();
+        if let Some(init) = node.initialize() {
+            init.resolve(self);
+        }
+        if let Some(condition) = node.condition() {
+            condition.resolve(self);
+        }
+        if let Some(increment) = node.increment() {
+            increment.resolve(self);
+        }
+        self.resolve_loop_body(node.label(), node.stmt());
+        let captured = self.end_scope();
+        node.set_captured(captured);
+    }
+    fn resolve_loop_body(&mut self, label: Option<&Token>, stmt: &AstNodeRef) {
+        if let Some(label) = label {
+            if self.loops.contains(&Some(label.text().clone())) {
+                self.error(
+                    label,
+                    &format!("label '{}' already used by an enclosing loop", label.text()),
+                );
+            }
+        }
+        self.loops.push(label.map(|l| l.text().clone()));
+        stmt.resolve(self);
+        self.loops.pop();
+    }
+    /// Checks that a break or continue has a loop to leave.
+    fn resolve_jump(&mut self, token: &Token, label: Option<&Token>) {
+        match label {
+            None if self.loops.is_empty() => {
+                self.error(token, &format!("{} statement out of loop", token.text()))
+            }
+            Some(label) if !self.loops.contains(&Some(label.text().clone())) => self.error(
+                label,
+                &format!("no enclosing loop labeled '{}'", label.text()),
+            ),
+            _ => {}
+        }
+    }
+    pub fn resolve_break_stmt(&mut self, node: &BreakStmt) {
+        self.resolve_jump(node.token(), node.label());
+    }
+    pub fn resolve_continue_stmt(&mut self, node: &ContinueStmt) {
+        self.resolve_jump(node.token(), node.label());
+    }
+    pub fn resolve_return_stmt(&mut self, node: &ReturnStmt) {
+        if self.function == FunctionType::None {
+            self.error(node.token(), "return statement out of function");
+        }
+        if let Some(e) = node.expr() {
+            if self.function == FunctionType::Initializer {
+                self.error(node.token(), "can't return a value from an initializer");
+            }
+            e.resolve(self);
+        }
+    }
+    pub fn resolve_throw_stmt(&mut self, node: &ThrowStmt) {
+        node.expr().resolve(self);
+    }
+    pub fn resolve_try_stmt(&mut self, node: &TryStmt) {
+        node.body().resolve(self);
+        if let Some((param, block)) = node.catch() {
+            self.begin_scope();
+            self.declare(param);
+            self.define(param);
+            block.resolve(self);
+            let captured = self.end_scope();
+            node.set_captured(captured);
+        }
+        if let Some(finally) = node.finally() {
+            finally.resolve(self);
+        }
+    }
+    pub fn resolve_print_stmt(&mut self, node: &PrintStmt) {
+        node.expr().resolve(self);
+    }
+    pub fn resolve_expr_stmt(&mut self, node: &ExprStmt) {
+        node.expr().resolve(self);
+    }
+    pub fn resolve_var_decl(&mut self, node: &VarDecl) {
+        self.declare(node.name());
+        if let Some(e) = node.expr() {
+            e.resolve(self);
+        }
+        self.define(node.name());
+    }
+    pub fn resolve_import_stmt(&mut self, node: &ImportStmt) {
+        self.declare(node.name());
+        self.define(node.name());
+    }
+    pub fn resolve_fun_decl(&mut self, node: &FunDecl) {
+        self.declare(node.name());
+        self.define(node.name());
+        let captured = self.resolve_function(node.params(), node.block(), FunctionType::Function);
+        node.set_captured(captured);
+    }
+    pub fn resolve_fun_def(&mut self, node: &FunDef) {
+        let captured = self.resolve_function(node.params(), node.block(), FunctionType::Function);
+        node.set_captured(captured);
+    }
+    pub fn resolve_fun_call(&mut self, node: &FunCall) {
+        node.callee().resolve(self);
+        self.resolve_all(node.args());
+    }
+    pub fn resolve_class_decl(&mut self, node: &ClassDecl) {
+        let enclosing = self.class;
+        self.class = ClassType::Class;
+        self.declare(node.name());
+        self.define(node.name());
+
+        if let Some(s) = node.superclass() {
+            self.class = ClassType::Subclass;
+            s.resolve(self);
+            self.begin_scope();
+            self.define_name("super");
+        }
+        self.begin_scope();
+        self.define_name("this");
+        for m in node.methods() {
+            let kind = if m.name().text() == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            let captured = self.resolve_function(m.params(), m.block(), kind);
+            m.set_captured(captured);
+        }
+        self.end_scope();
+        if node.superclass().is_some() {
+            self.end_scope();
+        }
+        self.class = enclosing;
+    }
+    pub fn resolve_get_expr(&mut self, node: &GetExpr) {
+        node.object().resolve(self);
+    }
+    pub fn resolve_set_expr(&mut self, node: &SetExpr) {
+        node.expr().resolve(self);
+        node.object().resolve(self);
+    }
+    pub fn resolve_super_expr(&mut self, node: &SuperExpr) {
+        match self.class {
+            ClassType::None => {
+                self.error(node.keyword(), "'super' used outside of a class");
+                return;
+            }
+            ClassType::Class => {
+                self.error(node.keyword(), "'super' used in a class with no superclass");
+                return;
+            }
+            ClassType::Subclass => {}
+        }
+        if let Some(depth) = self.local_depth(node.keyword().text()) {
+            node.set_depth(depth);
+        }
+    }
+    pub fn resolve_list_expr(&mut self, node: &ListExpr) {
+        self.resolve_all(node.items());
+    }
+    pub fn resolve_map_expr(&mut self, node: &MapExpr) {
+        for (key, value) in node.entries() {
+            key.resolve(self);
+            value.resolve(self);
+        }
+    }
+    pub fn resolve_interpolation_expr(&mut self, node: &InterpolationExpr) {
+        self.resolve_all(node.exprs());
+    }
+    pub fn resolve_index_expr(&mut self, node: &IndexExpr) {
+        node.object().resolve(self);
+        node.index().resolve(self);
+    }
+    pub fn resolve_index_set_expr(&mut self, node: &IndexSetExpr) {
+        node.object().resolve(self);
+        node.index().resolve(self);
+        node.expr().resolve(self);
+    }
+    pub fn resolve_program(&mut self, node: &Program) {
+        self.resolve_all(node.decs());
+    }
+    pub fn resolve_block(&mut self, node: &Block) {
+        self.begin_scope();
+        self.resolve_all(node.decs());
+        let captured = self.end_scope();
+        node.set_captured(captured);
+    }
+}
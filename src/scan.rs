@@ -1,32 +1,65 @@
-use crate::token::{Token, TokenKind};
+use crate::{
+    error::{LoxError, Span},
+    token::{Token, TokenKind},
+};
 
-const SINGLE_CHARS: &[char] = &['+', '-', '*', '/', ',', '}', '{', ')', '(', '.', ';'];
+const SINGLE_CHARS: &[char] = &[
+    '+', '-', '*', '/', ',', '}', '{', ')', '(', ']', '[', '.', ';', ':',
+];
 const DOUBLE_CHARS: &[char] = &['!', '=', '>', '<'];
 
 #[derive(Default)]
 struct Scanner {
-    errflag: bool,
+    errors: Vec<LoxError>,
+    unterminated: bool,
+    /// Unclosed '{' inside each `${...}` being scanned, innermost last.
+    interpolations: Vec<usize>,
     line: usize,
+    line_start: usize,
+    start: usize,
     cindex: usize,
     tokens: Vec<Token>,
     text: Vec<char>,
 }
 
 impl Scanner {
-    fn scan(&mut self, text: Vec<char>) -> Result<Vec<Token>, ()> {
+    fn scan(&mut self, text: Vec<char>) -> Result<Vec<Token>, Vec<LoxError>> {
         self.text = text;
         while self.cindex < self.text.len() {
+            self.start = self.cindex;
             let c = self.text[self.cindex];
             if c == '\n' {
                 self.line += 1;
+                self.line_start = self.cindex + 1;
+            } else if c == '/' && self.peek_next() == Some('/') {
+                self.skip_line_comment();
+            } else if c == '/' && self.peek_next() == Some('*') {
+                self.skip_block_comment();
+            } else if c == '}' && self.interpolations.last() == Some(&0) {
+                self.interpolations.pop();
+                self.scan_string();
             } else if SINGLE_CHARS.contains(&c) {
-                self.tokens.push(Scanner::scan_single_char(c, self.line));
+                if let Some(depth) = self.interpolations.last_mut() {
+                    match c {
+                        '{' => *depth += 1,
+                        '}' => *depth -= 1,
+                        _ => {}
+                    }
+                }
+                self.push_token(Scanner::scan_single_char(c), String::from(c));
             } else if DOUBLE_CHARS.contains(&c) {
                 if self.text.len() > self.cindex + 1 && self.text[self.cindex + 1] == '=' {
-                    self.tokens.push(Scanner::scan_double_char(c, self.line));
+                    self.push_token(Scanner::scan_double_char(c), format!("{}=", c));
+                    self.cindex += 1;
+                } else {
+                    self.push_token(Scanner::scan_single_char(c), String::from(c));
+                }
+            } else if c == '?' {
+                if self.peek_next() == Some('?') {
+                    self.push_token(TokenKind::QuestionQuestion, "??".to_string());
                     self.cindex += 1;
                 } else {
-                    self.tokens.push(Scanner::scan_single_char(c, self.line));
+                    self.push_token(TokenKind::Question, "?".to_string());
                 }
             } else if c.is_ascii_alphabetic() || c == '_' {
                 self.scan_identifier();
@@ -35,20 +68,77 @@ impl Scanner {
             } else if c == '"' {
                 self.scan_string();
             } else if c != ' ' && c != '\t' {
-                super::lox_error(self.line, "unsupported characters");
-                self.errflag = true;
+                self.error("unsupported characters");
             }
             self.cindex += 1;
         }
 
-        self.tokens
-            .push(Token::new(TokenKind::EOF, String::new(), self.line));
-        if self.errflag {
-            Err(())
-        } else {
+        self.start = self.cindex;
+        if !self.interpolations.is_empty() && !self.unterminated {
+            self.unterminated = true;
+            self.error("unterminated string interpolation");
+        }
+        self.push_token(TokenKind::EOF, String::new());
+        if self.errors.is_empty() {
             Ok(std::mem::take(&mut self.tokens))
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+    fn push_token(&mut self, kind: TokenKind, text: String) {
+        let span = Span::new(self.start, self.start + text.chars().count());
+        let column = self.start - self.line_start;
+        self.tokens
+            .push(Token::new(kind, text, self.line, column, span));
+    }
+    fn error(&mut self, text: &str) {
+        self.error_from(self.start, text);
+    }
+    /// An error from `start` to the character under the cursor, both on
+    /// the current line.
+    fn error_from(&mut self, start: usize, text: &str) {
+        let span = Span::new(start, (self.cindex + 1).min(self.text.len()));
+        let column = start - self.line_start;
+        self.errors
+            .push(LoxError::scan(self.line, column, span, text));
+    }
+    fn peek_next(&self) -> Option<char> {
+        self.text.get(self.cindex + 1).copied()
+    }
+    fn skip_line_comment(&mut self) {
+        while self.cindex + 1 < self.text.len() && self.text[self.cindex + 1] != '\n' {
+            self.cindex += 1;
         }
     }
+    fn skip_block_comment(&mut self) {
+        let (line, column) = (self.line, self.start - self.line_start);
+        let mut depth = 0;
+        while self.cindex < self.text.len() {
+            let c = self.text[self.cindex];
+            if c == '/' && self.peek_next() == Some('*') {
+                depth += 1;
+                self.cindex += 1;
+            } else if c == '*' && self.peek_next() == Some('/') {
+                depth -= 1;
+                self.cindex += 1;
+                if depth == 0 {
+                    return;
+                }
+            } else if c == '\n' {
+                self.line += 1;
+                self.line_start = self.cindex + 1;
+            }
+            self.cindex += 1;
+        }
+        self.unterminated = true;
+        let span = Span::new(self.start, self.text.len());
+        self.errors.push(LoxError::scan(
+            line,
+            column,
+            span,
+            "unterminated block comment",
+        ));
+    }
     fn scan_identifier(&mut self) {
         let mut buffer = String::new();
         while self.cindex < self.text.len()
@@ -57,28 +147,110 @@ impl Scanner {
             buffer.push(self.text[self.cindex]);
             self.cindex += 1;
         }
-        self.tokens.push(Scanner::scan_text(buffer, self.line));
+        self.push_token(Scanner::scan_text(&buffer), buffer);
         self.cindex -= 1;
     }
+    /// Decodes escape sequences, the token keeps its quotes and starts
+    /// where the string does even when it spans several lines. A string
+    /// with `${` stops there with an interpolation token, the '}' closing
+    /// the expression resumes it.
     fn scan_string(&mut self) {
-        let mut buffer = String::from(self.text[self.cindex]);
+        let (line, column) = (self.line, self.start - self.line_start);
+        let mut buffer = String::from('"');
         self.cindex += 1;
         loop {
-            if self.cindex >= self.text.len() {
-                super::lox_error(self.line, "unbalanced quotes");
-                self.errflag = true;
-                break;
-            } else if self.text[self.cindex] == '"' {
-                buffer.push('"');
-                self.tokens
-                    .push(Token::new(TokenKind::String, buffer, self.line));
-
-                break;
-            } else {
-                buffer.push(self.text[self.cindex]);
+            match self.text.get(self.cindex) {
+                None => {
+                    self.unterminated = true;
+                    let span = Span::new(self.start, self.text.len());
+                    self.errors
+                        .push(LoxError::scan(line, column, span, "unbalanced quotes"));
+                    return;
+                }
+                Some('"') => break,
+                Some('$') if self.peek_next() == Some('{') => {
+                    buffer.push('"');
+                    let span = Span::new(self.start, self.cindex + 2);
+                    self.tokens.push(Token::new(
+                        TokenKind::Interpolation,
+                        buffer,
+                        line,
+                        column,
+                        span,
+                    ));
+                    self.interpolations.push(0);
+                    self.cindex += 1;
+                    return;
+                }
+                Some('\\') => {
+                    if let Some(c) = self.scan_escape() {
+                        buffer.push(c);
+                    }
+                }
+                Some('\n') => {
+                    buffer.push('\n');
+                    self.line += 1;
+                    self.line_start = self.cindex + 1;
+                }
+                Some(c) => buffer.push(*c),
+            }
+            self.cindex += 1;
+        }
+        buffer.push('"');
+        let span = Span::new(self.start, self.cindex + 1);
+        self.tokens
+            .push(Token::new(TokenKind::String, buffer, line, column, span));
+    }
+    /// Decodes the escape sequence starting at the backslash under the
+    /// cursor and leaves the cursor on its last character. An invalid one
+    /// leaves it on the backslash, so what follows is read as usual.
+    fn scan_escape(&mut self) -> Option<char> {
+        let start = self.cindex;
+        let c = match self.text.get(start + 1) {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('$') => '$',
+            Some('u') => {
                 self.cindex += 1;
+                return self.scan_unicode(start);
+            }
+            Some(_) => {
+                self.cindex += 1;
+                self.error_from(start, "invalid escape sequence");
+                self.cindex = start;
+                return None;
+            }
+            None => return None,
+        };
+        self.cindex += 1;
+        Some(c)
+    }
+    /// Reads the `{...}` part of a `\u{...}` escape, with at most six hex
+    /// digits.
+    fn scan_unicode(&mut self, start: usize) -> Option<char> {
+        let mut digits = String::new();
+        if self.peek_next() == Some('{') {
+            self.cindex += 1;
+            while let Some(c) = self.peek_next().filter(|c| c.is_ascii_hexdigit()) {
+                digits.push(c);
+                self.cindex += 1;
+            }
+            if self.peek_next() == Some('}') {
+                self.cindex += 1;
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() <= 6)
+                    .and_then(char::from_u32);
+                if c.is_none() {
+                    self.error_from(start, "invalid unicode escape");
+                }
+                return c;
             }
         }
+        self.error_from(start, "unicode escape must look like \\u{41}");
+        None
     }
     fn scan_number(&mut self) {
         let mut dot_flag = false;
@@ -95,76 +267,123 @@ impl Scanner {
             buffer.push(self.text[self.cindex]);
             self.cindex += 1;
         }
-        self.tokens
-            .push(Token::new(TokenKind::Number, buffer, self.line));
+        self.push_token(TokenKind::Number, buffer);
         self.cindex -= 1;
     }
-    fn scan_single_char(c: char, line: usize) -> Token {
-        Token::new(
-            match c {
-                '+' => TokenKind::Plus,
-                '-' => TokenKind::Minus,
-                '*' => TokenKind::Star,
-                '/' => TokenKind::Slash,
-                '{' => TokenKind::LeftBrace,
-                '}' => TokenKind::RightBrace,
-                '(' => TokenKind::LeftParen,
-                ')' => TokenKind::RightParen,
-                ';' => TokenKind::Semicolon,
-                '.' => TokenKind::Dot,
-                '>' => TokenKind::Greater,
-                '<' => TokenKind::Less,
-                '=' => TokenKind::Equal,
-                '!' => TokenKind::Bang,
-                _ => TokenKind::Comma, // ','
-            },
-            String::from(c),
-            line,
-        )
+    fn scan_single_char(c: char) -> TokenKind {
+        match c {
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            ';' => TokenKind::Semicolon,
+            ':' => TokenKind::Colon,
+            '.' => TokenKind::Dot,
+            '>' => TokenKind::Greater,
+            '<' => TokenKind::Less,
+            '=' => TokenKind::Equal,
+            '!' => TokenKind::Bang,
+            _ => TokenKind::Comma, // ','
+        }
     }
 
-    fn scan_double_char(c: char, line: usize) -> Token {
-        Token::new(
-            match c {
-                '>' => TokenKind::GreaterEqual,
-                '<' => TokenKind::LessEqual,
-                '=' => TokenKind::EqualEqual,
-                _ => TokenKind::BangEqual, // '!'
-            },
-            format!("{}=", String::from(c)),
-            line,
-        )
+    fn scan_double_char(c: char) -> TokenKind {
+        match c {
+            '>' => TokenKind::GreaterEqual,
+            '<' => TokenKind::LessEqual,
+            '=' => TokenKind::EqualEqual,
+            _ => TokenKind::BangEqual, // '!'
+        }
     }
 
-    fn scan_text(text: String, line: usize) -> Token {
-        Token::new(
-            match text.as_str() {
-                "this" => TokenKind::This,
-                "true" => TokenKind::True,
-                "false" => TokenKind::False,
-                "while" => TokenKind::While,
-                "for" => TokenKind::For,
-                "break" => TokenKind::Break,
-                "class" => TokenKind::Class,
-                "return" => TokenKind::Return,
-                "if" => TokenKind::If,
-                "else" => TokenKind::Else,
-                "nil" => TokenKind::Nil,
-                "print" => TokenKind::Print,
-                "var" => TokenKind::Var,
-                "super" => TokenKind::Super,
-                "and" => TokenKind::And,
-                "or" => TokenKind::Or,
-                "fun" => TokenKind::Fun,
-                _ => TokenKind::Identifier,
-            },
-            text,
-            line,
-        )
+    fn scan_text(text: &str) -> TokenKind {
+        match text {
+            "this" => TokenKind::This,
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "while" => TokenKind::While,
+            "for" => TokenKind::For,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "class" => TokenKind::Class,
+            "return" => TokenKind::Return,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "nil" => TokenKind::Nil,
+            "print" => TokenKind::Print,
+            "var" => TokenKind::Var,
+            "super" => TokenKind::Super,
+            "and" => TokenKind::And,
+            "or" => TokenKind::Or,
+            "fun" => TokenKind::Fun,
+            "throw" => TokenKind::Throw,
+            "try" => TokenKind::Try,
+            "catch" => TokenKind::Catch,
+            "finally" => TokenKind::Finally,
+            "import" => TokenKind::Import,
+            "as" => TokenKind::As,
+            _ => TokenKind::Identifier,
+        }
     }
 }
 
-pub fn scan(text: Vec<char>) -> Result<Vec<Token>, ()> {
+pub fn scan(text: Vec<char>) -> Result<Vec<Token>, Vec<LoxError>> {
     let mut scanner = Scanner::default();
     scanner.scan(text)
 }
+
+/// Whether `text` stops in the middle of a declaration, e.g. inside a string
+/// or a block, so an interactive prompt should keep reading lines.
+pub fn is_incomplete(text: Vec<char>) -> bool {
+    let mut scanner = Scanner::default();
+    let tokens = scanner.scan(text);
+    if scanner.unterminated {
+        return true;
+    }
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(_) => return false,
+    };
+    let mut depth = 0;
+    for t in tokens.iter() {
+        match t.kind() {
+            TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => depth += 1,
+            TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+    // the last token before EOF
+    let last = tokens.iter().rev().nth(1).map(|t| t.kind());
+    matches!(
+        last,
+        Some(
+            TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Star
+                | TokenKind::Slash
+                | TokenKind::Bang
+                | TokenKind::BangEqual
+                | TokenKind::Equal
+                | TokenKind::EqualEqual
+                | TokenKind::Greater
+                | TokenKind::GreaterEqual
+                | TokenKind::Less
+                | TokenKind::LessEqual
+                | TokenKind::Question
+                | TokenKind::QuestionQuestion
+                | TokenKind::And
+                | TokenKind::Or
+                | TokenKind::Comma
+                | TokenKind::Dot
+        )
+    )
+}
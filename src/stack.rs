@@ -0,0 +1,47 @@
+use std::cell::Cell;
+
+/// Native stack scripts may use unless the host allows more, it fits the
+/// 2 MiB Rust gives spawned threads.
+const DEFAULT_LIMIT: usize = 1 << 20;
+
+thread_local! {
+    static LIMIT: Cell<usize> = const { Cell::new(DEFAULT_LIMIT) };
+    /// Where the outermost evaluation on this thread started.
+    static BASE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Clears the base when the outermost evaluation ends, panics included.
+struct Outermost;
+
+impl Drop for Outermost {
+    fn drop(&mut self) {
+        BASE.with(|b| b.set(None));
+    }
+}
+
+fn position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+pub fn set_limit(bytes: usize) {
+    LIMIT.with(|l| l.set(bytes));
+}
+
+/// Runs `f`, measuring the stack from here unless an evaluation further
+/// up, one importing a module or calling a native, already does.
+pub fn guarded<T>(f: impl FnOnce() -> T) -> T {
+    if BASE.with(|b| b.get()).is_some() {
+        return f();
+    }
+    BASE.with(|b| b.set(Some(position())));
+    let _outermost = Outermost;
+    f()
+}
+
+/// Whether evaluations have used up the stack they may, so one more call
+/// must fail instead of overflowing it.
+pub fn exhausted() -> bool {
+    let used = BASE.with(|b| b.get()).map(|base| base.abs_diff(position()));
+    used.is_some_and(|used| used > LIMIT.with(|l| l.get()))
+}
@@ -1,13 +1,19 @@
 use std::fmt::Display;
 
+use crate::error::Span;
+
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenKind {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
@@ -24,10 +30,14 @@ pub enum TokenKind {
     GreaterEqual,
     Less,
     LessEqual,
+    Question,
+    QuestionQuestion,
 
     // Literals.
     Identifier,
     String,
+    /// Part of a string literal followed by a `${...}` expression.
+    Interpolation,
     Number,
 
     // Keywords.
@@ -38,6 +48,7 @@ pub enum TokenKind {
     Fun,
     For,
     Break,
+    Continue,
     If,
     Nil,
     Or,
@@ -48,8 +59,17 @@ pub enum TokenKind {
     True,
     Var,
     While,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Import,
+    As,
 
     EOF,
+    /// Stands for the host application in the calls it makes, it has no
+    /// place in the source.
+    Host,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +78,8 @@ pub struct Token {
     kind: TokenKind,
     text: String,
     line: usize,
+    column: usize,
+    span: Span,
 }
 
 impl Display for Token {
@@ -77,6 +99,9 @@ impl Display for TokenKind {
             TokenKind::RightParen => "')'",
             TokenKind::LeftBrace => "'{'",
             TokenKind::RightBrace => "'}'",
+            TokenKind::LeftBracket => "'['",
+            TokenKind::RightBracket => "']'",
+            TokenKind::Colon => "':'",
             TokenKind::Dot => "'.'",
             TokenKind::Minus => "'-'",
             TokenKind::Plus => "'+'",
@@ -90,8 +115,11 @@ impl Display for TokenKind {
             TokenKind::GreaterEqual => "'>='",
             TokenKind::Less => "'<'",
             TokenKind::LessEqual => "'<='",
+            TokenKind::Question => "'?'",
+            TokenKind::QuestionQuestion => "'??'",
             TokenKind::Identifier => "identifier",
             TokenKind::String => "string literal",
+            TokenKind::Interpolation => "string interpolation",
             TokenKind::Number => "numeric literal",
             TokenKind::Class => "keyword 'class'",
             TokenKind::Else => "keyword 'else'",
@@ -108,15 +136,29 @@ impl Display for TokenKind {
             TokenKind::Var => "keyword 'var'",
             TokenKind::While => "keyword 'while'",
             TokenKind::Break => "keyword 'break'",
+            TokenKind::Continue => "keyword 'continue'",
+            TokenKind::Throw => "keyword 'throw'",
+            TokenKind::Try => "keyword 'try'",
+            TokenKind::Catch => "keyword 'catch'",
+            TokenKind::Finally => "keyword 'finally'",
+            TokenKind::Import => "keyword 'import'",
+            TokenKind::As => "keyword 'as'",
             TokenKind::EOF => "keyword 'eof'",
+            TokenKind::Host => "host call",
         };
         write!(f, "{}", s)
     }
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, text: String, line: usize) -> Token {
-        Token { kind, text, line }
+    pub fn new(kind: TokenKind, text: String, line: usize, column: usize, span: Span) -> Token {
+        Token {
+            kind,
+            text,
+            line,
+            column,
+            span,
+        }
     }
     pub fn kind(&self) -> TokenKind {
         self.kind
@@ -127,4 +169,20 @@ impl Token {
     pub fn text(&self) -> &String {
         &self.text
     }
+    pub fn column(&self) -> usize {
+        self.column
+    }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn host(text: String) -> Token {
+        Token::new(TokenKind::Host, text, 0, 0, Span::default())
+    }
+    /// One based line, 0 when the host made the call.
+    pub fn call_line(&self) -> usize {
+        match self.kind {
+            TokenKind::Host => 0,
+            _ => self.line + 1,
+        }
+    }
 }
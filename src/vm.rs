@@ -0,0 +1,698 @@
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
+
+use crate::{
+    ast::Ast,
+    chunk::{Chunk, OpCode, Proto, ProtoKind},
+    class::{Class, Instance},
+    compile::compile,
+    environment::{Env, Environment},
+    error::{Frame, LoxError, MAX_FRAMES},
+    exception::{caught_value, error_class, throw_error},
+    function::{all_natives, Arity, Function, Implementation, Runtime},
+    gc,
+    interpret::{Interpretor, Value},
+    list::List,
+    map::Map,
+    module::{import, LoaderRef},
+    output::OutputRef,
+    stack,
+    token::Token,
+};
+
+struct CallFrame {
+    /// `None` for the top level script.
+    function: Option<Function>,
+    proto: Rc<Proto>,
+    ip: usize,
+    /// Stack index of the callee, locals follow it.
+    base: usize,
+    env: Env,
+    /// Globals of the file the function comes from.
+    globals: Env,
+    /// One based line of the call site.
+    line: usize,
+}
+
+/// Where execution resumes when a runtime error happens inside a try
+/// statement.
+struct Handler {
+    /// Number of call frames when the handler was installed.
+    frames: usize,
+    ip: usize,
+    stack: usize,
+    env: Env,
+    raised: usize,
+    finally: bool,
+}
+
+/// How a native function called by the machine reaches back into it.
+struct NativeCall<'a> {
+    vm: &'a mut Vm,
+    /// Token of the call instruction.
+    token: Token,
+}
+
+impl Runtime for NativeCall<'_> {
+    fn call(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, LoxError> {
+        self.vm.call_nested(callee, args)
+    }
+    fn error(&self, message: &str) -> LoxError {
+        LoxError::runtime(&self.token, message)
+    }
+    fn print(&mut self, text: &str) -> Result<(), LoxError> {
+        self.vm
+            .output
+            .borrow_mut()
+            .print(text)
+            .map_err(|message| self.error(&message))
+    }
+}
+
+/// Stack based virtual machine running the bytecode produced by
+/// `compile`, an alternative to walking the tree with `Interpretor`.
+pub struct Vm {
+    globals: Env,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    handlers: Vec<Handler>,
+    /// Errors kept by finally handlers until their block is done.
+    raised: Vec<LoxError>,
+    error_class: Rc<Class>,
+    loader: LoaderRef,
+    output: OutputRef,
+    /// File being run, imports are relative to it.
+    path: Option<PathBuf>,
+}
+
+impl Vm {
+    /// A machine with globals of its own, running the file at `path`.
+    pub fn new(loader: LoaderRef, output: OutputRef, path: Option<PathBuf>) -> Vm {
+        let globals = Environment::new(None);
+        for (name, nf) in all_natives() {
+            globals.borrow_mut().init(name, Value::Function(nf));
+        }
+        let error_class = error_class();
+        globals
+            .borrow_mut()
+            .init("Error".to_string(), Value::Class(error_class.clone()));
+        Vm {
+            globals,
+            stack: vec![],
+            frames: vec![],
+            handlers: vec![],
+            raised: vec![],
+            error_class,
+            loader,
+            output,
+            path,
+        }
+    }
+    pub fn output(&self) -> OutputRef {
+        self.output.clone()
+    }
+    /// Sets the file imports are relative to, returns the previous one.
+    pub fn set_path(&mut self, path: Option<PathBuf>) -> Option<PathBuf> {
+        std::mem::replace(&mut self.path, path)
+    }
+    pub fn globals(&self) -> Env {
+        self.globals.clone()
+    }
+    /// Calls `callee` on behalf of the host through a script made of a
+    /// single call, `token` stands for the call site.
+    pub fn call(
+        &mut self,
+        callee: Value,
+        args: Vec<Value>,
+        token: &Token,
+    ) -> Result<Value, LoxError> {
+        let mut chunk = Chunk::default();
+        chunk.write(OpCode::Call(args.len() as u32), Some(token));
+        chunk.write(OpCode::Return, Some(token));
+        let script = Proto::new("host".to_string(), vec![], ProtoKind::Script, chunk);
+        self.stack.push(Value::Nil);
+        self.stack.push(callee);
+        self.stack.extend(args);
+        self.frames.push(CallFrame {
+            function: None,
+            proto: Rc::new(script),
+            ip: 0,
+            base: 0,
+            env: self.globals.clone(),
+            globals: self.globals.clone(),
+            line: 0,
+        });
+        let rsl = stack::guarded(|| self.execute(0));
+        self.stack.clear();
+        self.frames.clear();
+        self.handlers.clear();
+        self.raised.clear();
+        rsl
+    }
+    /// Compiles and runs `ast` against the globals of this machine, so
+    /// declarations persist between calls.
+    pub fn run(&mut self, ast: &Ast) -> Result<Value, LoxError> {
+        let script = Rc::new(compile(ast));
+        self.stack.push(Value::Nil);
+        self.frames.push(CallFrame {
+            function: None,
+            proto: script,
+            ip: 0,
+            base: 0,
+            env: self.globals.clone(),
+            globals: self.globals.clone(),
+            line: 0,
+        });
+        let rsl = stack::guarded(|| self.execute(0));
+        self.stack.clear();
+        self.frames.clear();
+        self.handlers.clear();
+        self.raised.clear();
+        rsl
+    }
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("no call frame")
+    }
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("no call frame")
+    }
+    fn chunk(&self) -> &Chunk {
+        self.frame().proto.chunk()
+    }
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("stack underflow")
+    }
+    /// Token of the instruction being executed.
+    fn token(&self) -> &Token {
+        let frame = self.frame();
+        frame
+            .proto
+            .chunk()
+            .token(frame.ip - 1)
+            .expect("instruction without a source position")
+    }
+    fn trace(&self) -> Vec<Frame> {
+        self.frames
+            .iter()
+            .rev()
+            .filter_map(|f| {
+                f.function
+                    .as_ref()
+                    .map(|fun| Frame::new(fun.name().clone(), f.line))
+            })
+            .collect()
+    }
+    /// File of the module the current frame's code comes from, `None` for
+    /// the script being run.
+    fn file(&self) -> Option<PathBuf> {
+        self.loader.borrow().file_of(&self.frame().globals)
+    }
+    fn error(&self, message: &str) -> LoxError {
+        LoxError::runtime(self.token(), message).with_trace(self.trace())
+    }
+    fn undefined(&self, name: u32) -> LoxError {
+        self.error(&format!("undefind variable '{}'", self.chunk().name(name)))
+    }
+    /// Runs until the frames above the first `floor` ones return, or fail
+    /// with no handler of their own left.
+    fn execute(&mut self, floor: usize) -> Result<Value, LoxError> {
+        loop {
+            // the frame that failed is still on top
+            let rsl = self
+                .run_until_error(floor)
+                .map_err(|err| err.placed_in(self.file()));
+            match rsl {
+                Err(err)
+                    if err.is_runtime()
+                        && self.handlers.last().is_some_and(|h| h.frames > floor) =>
+                {
+                    let handler = self.handlers.pop().expect("no handler");
+                    self.unwind(handler, err);
+                }
+                rsl => return rsl,
+            }
+        }
+    }
+    /// Runs `callee` to completion for a native function, on top of the
+    /// frames already running.
+    fn call_nested(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value, LoxError> {
+        // each native calling back into Lox recurses on the native stack
+        if stack::exhausted() {
+            return Err(self.error("stack overflow"));
+        }
+        let floor = self.frames.len();
+        let slot = self.stack.len();
+        let raised = self.raised.len();
+        let arg_count = args.len();
+        self.push(callee.clone());
+        self.stack.extend(args);
+        let rsl = self.call_value(arg_count).and_then(|()| {
+            if self.frames.len() == floor {
+                Ok(self.pop())
+            } else {
+                self.execute(floor)
+            }
+        });
+        if rsl.is_err() {
+            self.frames.truncate(floor);
+            self.stack.truncate(slot);
+            self.raised.truncate(raised);
+        }
+        rsl
+    }
+    /// Drops what the failed code left behind and resumes at `handler`.
+    fn unwind(&mut self, handler: Handler, err: LoxError) {
+        self.frames.truncate(handler.frames);
+        self.stack.truncate(handler.stack);
+        self.raised.truncate(handler.raised);
+        let frame = self.frame_mut();
+        frame.env = handler.env;
+        frame.ip = handler.ip;
+        if handler.finally {
+            self.raised.push(err);
+        } else {
+            let value = caught_value(&err, &self.error_class);
+            self.push(value);
+        }
+    }
+    fn run_until_error(&mut self, floor: usize) -> Result<Value, LoxError> {
+        loop {
+            let frame = self.frame_mut();
+            let op = frame.proto.chunk().code()[frame.ip];
+            frame.ip += 1;
+            match op {
+                OpCode::Constant(index) => {
+                    let value = self.chunk().constant(index).clone();
+                    self.push(value);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Boolean(true)),
+                OpCode::False => self.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::PopN(count) => {
+                    self.stack.truncate(self.stack.len() - count as usize);
+                }
+                OpCode::Dup => self.push(self.peek().clone()),
+                OpCode::GetLocal(slot) => {
+                    let value = self.stack[self.frame().base + slot as usize].clone();
+                    self.push(value);
+                }
+                OpCode::SetLocal(slot) => {
+                    let index = self.frame().base + slot as usize;
+                    self.stack[index] = self.peek().clone();
+                }
+                OpCode::GetEnv(depth, name) => {
+                    let frame = self.frame();
+                    let name_text = frame.proto.chunk().name(name);
+                    match Environment::get_at(&frame.env, depth as usize, name_text) {
+                        Some(value) => self.push(value),
+                        None => return Err(self.undefined(name)),
+                    }
+                }
+                OpCode::SetEnv(depth, name) => {
+                    let frame = self.frame();
+                    let name_text = frame.proto.chunk().name(name).clone();
+                    let value = self.peek().clone();
+                    if !Environment::assign_at(&frame.env, depth as usize, name_text, value) {
+                        return Err(self.undefined(name));
+                    }
+                }
+                OpCode::DefineEnv(name) => {
+                    let value = self.pop();
+                    let frame = self.frame();
+                    let name = frame.proto.chunk().name(name).clone();
+                    frame.env.borrow_mut().init(name, value);
+                }
+                OpCode::GetGlobal(name) => {
+                    let value = self.frame().globals.borrow().get(self.chunk().name(name));
+                    match value {
+                        Some(value) => self.push(value),
+                        None => return Err(self.undefined(name)),
+                    }
+                }
+                OpCode::SetGlobal(name) => {
+                    let name_text = self.chunk().name(name).clone();
+                    let value = self.peek().clone();
+                    if !self.frame().globals.borrow_mut().assign(name_text, value) {
+                        return Err(self.undefined(name));
+                    }
+                }
+                OpCode::DefineGlobal(name) => {
+                    let value = self.pop();
+                    let name = self.chunk().name(name).clone();
+                    self.frame().globals.borrow_mut().init(name, value);
+                }
+                OpCode::PushEnv => {
+                    let frame = self.frame_mut();
+                    frame.env = Environment::new(Some(frame.env.clone()));
+                }
+                OpCode::PopEnv => {
+                    let frame = self.frame_mut();
+                    let parent = frame
+                        .env
+                        .borrow()
+                        .parent()
+                        .expect("popped the global scope");
+                    frame.env = parent;
+                }
+                OpCode::GetProperty(name) => {
+                    let object = self.pop();
+                    let name = self.chunk().name(name);
+                    let instance = match &object {
+                        Value::Instance(instance) => instance.clone(),
+                        Value::Module(module) => match module.get(name) {
+                            Ok(value) => {
+                                self.push(value);
+                                continue;
+                            }
+                            Err(message) => return Err(self.error(&message)),
+                        },
+                        _ => return Err(self.error("only instances have properties")),
+                    };
+                    let field = instance.borrow().field(name);
+                    let value = match field {
+                        Some(value) => value,
+                        None => match instance.borrow().class().find_method(name) {
+                            Some(method) => Value::Function(method.bind(object)),
+                            None => {
+                                return Err(self.error(&format!("undefined property '{}'", name)))
+                            }
+                        },
+                    };
+                    self.push(value);
+                }
+                OpCode::ExpectInstance => {
+                    if !matches!(self.peek(), Value::Instance(_)) {
+                        return Err(self.error("only instances have fields"));
+                    }
+                }
+                OpCode::SetProperty(name) => {
+                    let value = self.pop();
+                    if let Value::Instance(instance) = self.pop() {
+                        let name = self.chunk().name(name).clone();
+                        instance.borrow_mut().set_field(name, value.clone());
+                    }
+                    self.push(value);
+                }
+                OpCode::GetSuper(name) => {
+                    let superclass = match self.pop() {
+                        Value::Class(c) => c,
+                        _ => return Err(self.error("'super' used outside of a subclass")),
+                    };
+                    let instance = self.pop();
+                    let name = self.chunk().name(name);
+                    match superclass.find_method(name) {
+                        Some(method) => self.push(Value::Function(method.bind(instance))),
+                        None => return Err(self.error(&format!("undefined property '{}'", name))),
+                    }
+                }
+                OpCode::List(count) => {
+                    let items = self.stack.split_off(self.stack.len() - count as usize);
+                    self.push(Value::List(List::create(items)));
+                }
+                OpCode::Concat(count) => {
+                    let parts = self.stack.split_off(self.stack.len() - count as usize);
+                    let text = parts.iter().map(|p| p.to_string()).collect::<String>();
+                    self.push(Value::String(text));
+                }
+                OpCode::Map(count) => {
+                    let entries = self.stack.split_off(self.stack.len() - 2 * count as usize);
+                    let map = Map::create();
+                    for pair in entries.chunks(2) {
+                        if let Err(message) = map.borrow_mut().set(&pair[0], pair[1].clone()) {
+                            return Err(self.error(&message));
+                        }
+                    }
+                    self.push(Value::Map(map));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    match object.get_index(&index) {
+                        Ok(value) => self.push(value),
+                        Err(message) => return Err(self.error(&message)),
+                    }
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    if let Err(message) = object.set_index(&index, value.clone()) {
+                        return Err(self.error(&message));
+                    }
+                    self.push(value);
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Boolean(a == b));
+                }
+                OpCode::NotEqual => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Boolean(a != b));
+                }
+                OpCode::Greater => self.arithmetic(|a, b| Value::Boolean(a > b))?,
+                OpCode::GreaterEqual => self.arithmetic(|a, b| Value::Boolean(a >= b))?,
+                OpCode::Less => self.arithmetic(|a, b| Value::Boolean(a < b))?,
+                OpCode::LessEqual => self.arithmetic(|a, b| Value::Boolean(a <= b))?,
+                OpCode::Subtract => self.arithmetic(|a, b| Value::Number(a - b))?,
+                OpCode::Multiply => self.arithmetic(|a, b| Value::Number(a * b))?,
+                OpCode::Divide => self.arithmetic(|a, b| Value::Number(a / b))?,
+                OpCode::Add => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    let value = match (a, b) {
+                        (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                        (Value::String(a), Value::String(b)) => Value::String(a + &b),
+                        _ => {
+                            return Err(self
+                                .error("operator '+' can only be used on 2 numbers or 2 strings"))
+                        }
+                    };
+                    self.push(value);
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Boolean(!value.truth()));
+                }
+                OpCode::Negate => match self.pop() {
+                    Value::Number(num) => self.push(Value::Number(-num)),
+                    _ => return Err(self.error("expected number after '-'")),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    let printed = self.output.borrow_mut().print(&value.to_string());
+                    printed.map_err(|message| self.error(&message))?;
+                }
+                OpCode::Jump(target) => self.frame_mut().ip = target as usize,
+                OpCode::JumpIfFalse(target) => {
+                    if !self.pop().truth() {
+                        self.frame_mut().ip = target as usize;
+                    }
+                }
+                OpCode::Call(arg_count) => self.call_value(arg_count as usize)?,
+                OpCode::Closure(index) => {
+                    let frame = self.frame();
+                    let proto = frame.proto.chunk().function(index).clone();
+                    let name = proto.name().clone();
+                    let params = proto.params().clone();
+                    let env = frame.env.clone();
+                    let kind = proto.kind();
+                    let code = Implementation::Compiled(proto);
+                    let function = match kind {
+                        ProtoKind::Method | ProtoKind::Initializer => Function::create_method(
+                            name,
+                            code,
+                            params,
+                            env,
+                            kind == ProtoKind::Initializer,
+                        ),
+                        _ => Function::create(name, code, params, Some(env)),
+                    };
+                    self.push(Value::Function(function));
+                }
+                OpCode::Inherit => {
+                    if !matches!(self.peek(), Value::Class(_)) {
+                        return Err(self.error("superclass must be a class"));
+                    }
+                }
+                OpCode::Class(name, count, inherits) => {
+                    let methods = self
+                        .stack
+                        .split_off(self.stack.len() - count as usize)
+                        .into_iter()
+                        .filter_map(|m| match m {
+                            Value::Function(f) => Some((f.name().clone(), f)),
+                            _ => None,
+                        })
+                        .collect::<HashMap<String, Function>>();
+                    let superclass = match inherits {
+                        true => match self.pop() {
+                            Value::Class(c) => Some(c),
+                            _ => None,
+                        },
+                        false => None,
+                    };
+                    let name = self.chunk().name(name).clone();
+                    self.push(Value::Class(Class::create(name, superclass, methods)));
+                }
+                OpCode::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().expect("no call frame");
+                    while self
+                        .handlers
+                        .last()
+                        .is_some_and(|h| h.frames > self.frames.len())
+                    {
+                        self.handlers.pop();
+                    }
+                    let value = match &frame.function {
+                        Some(f) if f.is_initializer() => f
+                            .closure()
+                            .and_then(|c| c.borrow().get(&"this".to_string()))
+                            .unwrap_or(Value::Nil),
+                        _ => value,
+                    };
+                    self.stack.truncate(frame.base);
+                    if self.frames.len() == floor {
+                        return Ok(value);
+                    }
+                    self.push(value);
+                }
+                OpCode::Try(target, finally) => {
+                    let handler = Handler {
+                        frames: self.frames.len(),
+                        ip: target as usize,
+                        stack: self.stack.len(),
+                        env: self.frame().env.clone(),
+                        raised: self.raised.len(),
+                        finally,
+                    };
+                    self.handlers.push(handler);
+                }
+                OpCode::EndTry => {
+                    self.handlers.pop();
+                }
+                OpCode::Throw => {
+                    let value = self.pop();
+                    return Err(throw_error(self.token(), value).with_trace(self.trace()));
+                }
+                OpCode::Rethrow => {
+                    return Err(self.raised.pop().expect("no error to raise again"));
+                }
+                OpCode::DropRaised => {
+                    self.raised.pop();
+                }
+                OpCode::Import(path) => {
+                    let path = self.chunk().name(path).clone();
+                    let importer = self
+                        .loader
+                        .borrow()
+                        .file_of(&Environment::root(&self.frame().env))
+                        .or_else(|| self.path.clone());
+                    let loader = self.loader.clone();
+                    let output = self.output.clone();
+                    let token = self.token().clone();
+                    let module = import(
+                        &self.loader,
+                        importer.as_deref(),
+                        &token,
+                        &path,
+                        |ast, path| {
+                            let mut vm = Vm::new(loader, output, Some(path));
+                            vm.run(ast)?;
+                            Ok(vm.globals.clone())
+                        },
+                    );
+                    match module {
+                        Ok(module) => self.push(Value::Module(module)),
+                        Err(err) => return Err(err.with_trace(self.trace())),
+                    }
+                }
+            }
+        }
+    }
+    fn arithmetic(&mut self, op: fn(f64, f64) -> Value) -> Result<(), LoxError> {
+        let b = self.pop();
+        let a = self.pop();
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.push(op(a, b));
+                Ok(())
+            }
+            _ => Err(self.error("arithmatic operators can only be used on numbers")),
+        }
+    }
+    fn call_value(&mut self, arg_count: usize) -> Result<(), LoxError> {
+        gc::maybe_collect();
+        let slot = self.stack.len() - 1 - arg_count;
+        match self.stack[slot].clone() {
+            Value::Function(fun) => self.call_function(fun, arg_count),
+            Value::Class(class) => {
+                let instance = Value::Instance(Instance::create(class.clone()));
+                self.stack[slot] = instance.clone();
+                match class.find_method("init") {
+                    Some(init) => self.call_function(init.bind(instance), arg_count),
+                    None => match Arity::Fixed(0).check(arg_count) {
+                        Some(pcount) => Err(self.arity_error(arg_count, pcount)),
+                        None => Ok(()),
+                    },
+                }
+            }
+            callee => Err(self.error(&format!("{} is not callable", callee))),
+        }
+    }
+    fn call_function(&mut self, callee: Function, arg_count: usize) -> Result<(), LoxError> {
+        if let Some(pcount) = callee.arity().check(arg_count) {
+            return Err(self.arity_error(arg_count, pcount));
+        }
+        let line = self.token().call_line();
+        match callee.code().clone() {
+            Implementation::NativeImpl(nf, _) => {
+                let args = self.stack.split_off(self.stack.len() - arg_count);
+                let args = callee.receiver().into_iter().chain(args).collect();
+                self.pop();
+                let token = self.token().clone();
+                match nf(&mut NativeCall { vm: self, token }, args) {
+                    Ok(value) => self.push(value),
+                    Err(err) => {
+                        let mut trace = vec![Frame::new(callee.name().clone(), line)];
+                        trace.extend(self.trace());
+                        return Err(err.with_trace(trace));
+                    }
+                }
+            }
+            Implementation::Compiled(proto) => {
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(self.error("stack overflow"));
+                }
+                let env = callee.closure().unwrap_or_else(|| self.globals.clone());
+                let globals = Environment::root(&env);
+                self.frames.push(CallFrame {
+                    function: Some(callee),
+                    proto,
+                    ip: 0,
+                    base: self.stack.len() - 1 - arg_count,
+                    env,
+                    globals,
+                    line,
+                });
+            }
+            Implementation::LoxImpl(_) => {
+                return Err(self.error("function belongs to another engine"));
+            }
+        }
+        Ok(())
+    }
+    fn arity_error(&self, arg_count: usize, pcount: usize) -> LoxError {
+        Interpretor::arity_error(self.token(), arg_count, pcount).with_trace(self.trace())
+    }
+}
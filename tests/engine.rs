@@ -0,0 +1,267 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use arlox::{Arity, Lox, LoxError, Value};
+
+fn engines() -> [Lox; 2] {
+    [Lox::new(), Lox::with_vm()]
+}
+
+/// Bytes written to an engine's output, readable after the engine took the
+/// sink.
+#[derive(Clone, Default)]
+struct Sink(Rc<RefCell<Vec<u8>>>);
+
+impl Sink {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What `report_errors` writes for the errors of `source`.
+fn report(lox: &mut Lox, source: &str) -> String {
+    let err = Sink::default();
+    lox.set_error_output(err.clone());
+    let errors = lox.eval(source).expect_err("source fails");
+    lox.report_errors(source, &errors);
+    err.text()
+}
+
+fn define_f(lox: &mut Lox) -> Value {
+    lox.eval("fun f(n) { return n + 1; }").expect("f is valid");
+    lox.get_global("f").expect("f is defined")
+}
+
+#[test]
+fn function_of_another_engine() {
+    for (mut from, mut to) in [(Lox::new(), Lox::with_vm()), (Lox::with_vm(), Lox::new())] {
+        let f = define_f(&mut from);
+        to.set_global("g", f.clone());
+        let errors = to.eval("g(3);").expect_err("g can't run here");
+        assert_eq!(errors[0].message(), "function belongs to another engine");
+
+        let err = to.call(&f, vec![Value::Number(3.0)]).unwrap_err();
+        assert_eq!(err.message(), "function belongs to another engine");
+    }
+}
+
+#[test]
+fn host_call_errors_have_no_position() {
+    for mut lox in engines() {
+        let f = define_f(&mut lox);
+        assert!(lox.call(&f, vec![Value::Number(1.0)]) == Ok(Value::Number(2.0)));
+
+        let err = lox.call(&f, vec![]).unwrap_err();
+        assert_eq!((err.line(), err.column()), (0, 0));
+        assert_eq!(
+            err.to_string(),
+            "Runtime error: invalid number of arguments (0) passed to function which takes 1 params"
+        );
+
+        let err = lox.call(&Value::Number(1.0), vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "Runtime error: 1 is not callable");
+    }
+}
+
+#[test]
+fn deep_recursion_overflows() {
+    for mut lox in engines() {
+        let errors = lox
+            .eval("fun dive(n) { return dive(n + 1); } dive(0);")
+            .expect_err("dive never returns");
+        assert!(matches!(errors[0], LoxError::Runtime(_)));
+        assert_eq!(errors[0].message(), "stack overflow");
+    }
+}
+
+#[test]
+fn trace_lists_calls_innermost_first() {
+    let source = "fun inner() { return nil + 1; }\nfun outer() { return inner(); }\nouter();";
+    for mut lox in engines() {
+        assert_eq!(
+            report(&mut lox, source),
+            "Runtime error [line 1:26]: operator '+' can only be used on 2 numbers or 2 strings
+    fun inner() { return nil + 1; }
+                             ^
+  in inner called at line 2
+  in outer called at line 3
+"
+        );
+    }
+}
+
+#[test]
+fn overflow_trace_collapses_repeated_frames() {
+    let source = "fun dive(n) {\n  return dive(n + 1);\n}\ndive(0);";
+    for mut lox in engines() {
+        let text = report(&mut lox, source);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "Runtime error [line 2:14]: stack overflow");
+        assert_eq!(lines[3], "  in dive called at line 2");
+        assert!(lines[4].starts_with("  ... previous frame repeated "));
+        assert!(lines[4].ends_with(" more times"));
+        assert_eq!(lines[5], "  in dive called at line 4");
+        assert_eq!(lines.len(), 6);
+    }
+}
+
+#[test]
+fn globals_round_trip() {
+    for mut lox in engines() {
+        lox.set_global("answer", Value::Number(42.0));
+        assert!(lox.get_global("answer") == Some(Value::Number(42.0)));
+        assert!(lox.eval("answer + 1").unwrap() == Some(Value::Number(43.0)));
+
+        lox.eval("var greeting = \"hi\";").unwrap();
+        assert!(lox.get_global("greeting") == Some(Value::String("hi".to_string())));
+        lox.set_global("greeting", Value::Boolean(true));
+        assert!(lox.eval("greeting").unwrap() == Some(Value::Boolean(true)));
+        assert!(lox.get_global("missing").is_none());
+    }
+}
+
+#[test]
+fn globals_are_sorted_by_name() {
+    for mut lox in engines() {
+        lox.eval("var b = 2; var a = 1;").unwrap();
+        let globals = lox.globals();
+        let names = globals.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert!(globals.contains(&("a".to_string(), Value::Number(1.0))));
+        assert!(globals.contains(&("b".to_string(), Value::Number(2.0))));
+        assert!(names.contains(&"clock"));
+    }
+}
+
+#[test]
+fn host_calls_functions_and_classes() {
+    for mut lox in engines() {
+        lox.eval(
+            "fun add(a, b) { return a + b; }
+class Point {
+  init(x, y) { this.x = x; this.y = y; }
+  sum() { return this.x + this.y; }
+}",
+        )
+        .unwrap();
+        let add = lox.get_global("add").unwrap();
+        let sum = lox.call(&add, vec![Value::Number(1.0), Value::Number(2.0)]);
+        assert!(sum == Ok(Value::Number(3.0)));
+
+        let point = lox.get_global("Point").unwrap();
+        let p = lox
+            .call(&point, vec![Value::Number(3.0), Value::Number(4.0)])
+            .expect("Point takes 2 arguments");
+        lox.set_global("p", p);
+        assert!(lox.eval("p.sum()").unwrap() == Some(Value::Number(7.0)));
+
+        let err = lox.call(&point, vec![]).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "invalid number of arguments (0) passed to function which takes 2 params"
+        );
+    }
+}
+
+#[test]
+fn natives_mutate_host_state() {
+    for mut lox in engines() {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let record = seen.clone();
+        lox.define_native("record", Arity::Fixed(1), move |_, args| {
+            record.borrow_mut().push(args[0].clone());
+            Ok(Value::Number(record.borrow().len() as f64))
+        });
+        lox.eval("record(1); record(\"two\");").unwrap();
+        assert!(lox.eval("record(nil)").unwrap() == Some(Value::Number(3.0)));
+        assert!(
+            *seen.borrow()
+                == vec![
+                    Value::Number(1.0),
+                    Value::String("two".to_string()),
+                    Value::Nil
+                ]
+        );
+    }
+}
+
+#[test]
+fn natives_with_at_least_arity() {
+    for mut lox in engines() {
+        lox.define_native("count", Arity::AtLeast(1), |_, args| {
+            Ok(Value::Number(args.len() as f64))
+        });
+        assert!(lox.eval("count(1)").unwrap() == Some(Value::Number(1.0)));
+        assert!(lox.eval("count(1, 2, 3)").unwrap() == Some(Value::Number(3.0)));
+        let errors = lox.eval("count();").unwrap_err();
+        assert_eq!(
+            errors[0].message(),
+            "invalid number of arguments (0) passed to function which takes 1 params"
+        );
+    }
+}
+
+#[test]
+fn natives_call_back_into_lox() {
+    for mut lox in engines() {
+        lox.define_native("twice", Arity::Fixed(2), |runtime, args| {
+            let once = runtime.call(&args[0], vec![args[1].clone()])?;
+            runtime.call(&args[0], vec![once])
+        });
+        lox.eval(
+            "fun adder(n) {
+  fun add(x) { return x + n; }
+  return add;
+}",
+        )
+        .unwrap();
+        assert!(lox.eval("twice(adder(10), 1)").unwrap() == Some(Value::Number(21.0)));
+    }
+}
+
+#[test]
+fn native_errors_are_caught_by_lox() {
+    for mut lox in engines() {
+        lox.define_native("fail", Arity::Fixed(0), |runtime, _| {
+            Err(runtime.error("failed on purpose"))
+        });
+        lox.eval("var message; try { fail(); } catch (e) { message = e.message; }")
+            .unwrap();
+        let message = lox.get_global("message");
+        assert!(message == Some(Value::String("failed on purpose".to_string())));
+    }
+}
+
+#[test]
+fn output_and_errors_go_to_their_sinks() {
+    for mut lox in engines() {
+        let (out, err) = (Sink::default(), Sink::default());
+        lox.set_output(out.clone());
+        lox.set_error_output(err.clone());
+        let source =
+            "print \"printed\";\nlog(\"logged\", 1);\nfun fail() {\n  return nil + 1;\n}\nfail();";
+        let errors = lox.eval(source).unwrap_err();
+        lox.report_errors(source, &errors);
+
+        assert_eq!(out.text(), "printed\nlogged 1 \n");
+        assert_eq!(
+            err.text(),
+            "Runtime error [line 4:14]: operator '+' can only be used on 2 numbers or 2 strings
+      return nil + 1;
+                 ^
+  in fail called at line 6
+"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::rc::Rc;
+
+use arlox::{collect_garbage, Lox, Value};
+
+fn engines() -> [Lox; 2] {
+    [Lox::new(), Lox::with_vm()]
+}
+
+fn eval(lox: &mut Lox, source: &str) -> Value {
+    lox.eval(source)
+        .expect("source is valid")
+        .expect("source is an expression")
+}
+
+/// Runs `setup`, which builds two cycles holding the lists `kept` and
+/// `dropped`, then clears the global `dropped`. Only the list of `kept`
+/// must survive a collection.
+fn check_cycle(setup: &str, kept: &str, dropped: &str) {
+    for mut lox in engines() {
+        lox.eval(setup).expect("setup is valid");
+        let Value::List(survivor) = eval(&mut lox, kept) else {
+            panic!("{} is not a list", kept);
+        };
+        let garbage = match eval(&mut lox, dropped) {
+            Value::List(list) => Rc::downgrade(&list),
+            _ => panic!("{} is not a list", dropped),
+        };
+        lox.eval("dropped = nil;").expect("dropped is defined");
+
+        assert!(collect_garbage() > 0);
+        assert!(garbage.upgrade().is_none());
+        assert_eq!(survivor.borrow().items()[0], Value::Number(1.0));
+        let Value::List(again) = eval(&mut lox, kept) else {
+            panic!("{} is gone", kept);
+        };
+        assert!(Rc::ptr_eq(&survivor, &again));
+    }
+}
+
+#[test]
+fn closure_and_its_environment() {
+    let setup = "
+fun make() {
+  var items = [1];
+  fun get() { return items; }
+  return get;
+}
+var kept = make();
+var dropped = make();";
+    check_cycle(setup, "kept()", "dropped()");
+}
+
+#[test]
+fn instance_in_its_own_field() {
+    let setup = "
+class Node {}
+var kept = Node();
+kept.next = kept;
+kept.items = [1];
+var dropped = Node();
+dropped.next = dropped;
+dropped.items = [1];";
+    check_cycle(setup, "kept.next.items", "dropped.next.items");
+}
+
+#[test]
+fn list_containing_itself() {
+    let setup = "
+var kept = [1];
+push(kept, kept);
+var dropped = [1];
+push(dropped, dropped);";
+    check_cycle(setup, "kept[1]", "dropped[1]");
+}
+
+#[test]
+fn bound_native_method() {
+    let setup = "
+var kept = Error(\"kept\");
+kept.init = kept.init;
+kept.items = [1];
+var dropped = Error(\"dropped\");
+dropped.init = dropped.init;
+dropped.items = [1];";
+    check_cycle(setup, "kept.items", "dropped.items");
+}
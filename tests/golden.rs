@@ -0,0 +1,17 @@
+use std::path::Path;
+
+fn run(vm: bool) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let report = arlox::run_tests(&dir, vm).expect("can't read the test scripts");
+    assert!(report.ok(), "\n{}", report);
+}
+
+#[test]
+fn tree_walker() {
+    run(false);
+}
+
+#[test]
+fn virtual_machine() {
+    run(true);
+}
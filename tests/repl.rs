@@ -0,0 +1,126 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use arlox::is_incomplete;
+
+#[test]
+fn unbalanced_brackets_continue() {
+    assert!(is_incomplete("fun f() {"));
+    assert!(is_incomplete("if (true) { print (1"));
+    assert!(is_incomplete("var l = [1,\n2"));
+    assert!(is_incomplete("var m = {\"a\": [1, 2]"));
+}
+
+#[test]
+fn unterminated_string_and_comment_continue() {
+    assert!(is_incomplete("print \"two\nlines"));
+    assert!(is_incomplete("print \"${1 +"));
+    assert!(is_incomplete("/* a comment"));
+    assert!(is_incomplete("/* outer /* inner */"));
+}
+
+#[test]
+fn trailing_operator_continues() {
+    assert!(is_incomplete("print 1 +"));
+    assert!(is_incomplete("var x ="));
+}
+
+#[test]
+fn closing_bracket_without_opener_is_complete() {
+    assert!(!is_incomplete("}"));
+    assert!(!is_incomplete(")"));
+    assert!(!is_incomplete("]"));
+    assert!(!is_incomplete("print 1; }"));
+}
+
+#[test]
+fn complete_input() {
+    assert!(!is_incomplete(""));
+    assert!(!is_incomplete("print 1;"));
+    assert!(!is_incomplete("1 + 2"));
+    assert!(!is_incomplete("fun f() {\n  return [1, (2)];\n}"));
+    assert!(!is_incomplete("print \"{ not a brace\"; // (\n/* [ */"));
+}
+
+/// Runs the REPL in `dir` on `input`, returns what it wrote to stdout and
+/// stderr.
+fn session(dir: &Path, input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("can't start the repl");
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(input.as_bytes())
+        .expect("can't write input");
+    drop(stdin);
+    let output = child.wait_with_output().expect("the repl runs");
+    assert!(output.status.success());
+    (
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+/// A fresh directory for the files of `test`.
+fn scratch(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("arlox-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("can't create a scratch directory");
+    dir
+}
+
+#[test]
+fn env_and_reset() {
+    let (out, _) = session(
+        &scratch("env"),
+        "var answer = 42;\n:env\n:reset\n:env\nanswer;\n",
+    );
+    assert_eq!(out.matches("answer = 42").count(), 1);
+    assert!(out.contains("clock = [Native Function]"));
+    assert!(!out.contains("> 42"));
+}
+
+#[test]
+fn tokens_and_ast() {
+    let (out, _) = session(&scratch("tokens"), ":tokens print 1;\n:ast 1 + 2 * 3\n");
+    assert!(out.contains("1:1\tPrint\tprint"));
+    assert!(out.contains("1:7\tNumber\t1"));
+    assert!(out.contains("1:8\tSemicolon\t;"));
+    assert!(out.contains("(+ 1 (* 2 3))"));
+}
+
+#[test]
+fn load_runs_in_the_session() {
+    let dir = scratch("load");
+    fs::write(
+        dir.join("script.lox"),
+        "var loaded = \"yes\";\nprint \"ran\";\n",
+    )
+    .unwrap();
+    let (out, err) = session(&dir, ":load script.lox\nprint loaded;\n:load missing.lox\n");
+    assert!(out.contains("ran"));
+    assert!(out.contains("yes"));
+    assert!(err.contains("can't open file missing.lox"));
+}
+
+#[test]
+fn load_keeps_the_import_base() {
+    let dir = scratch("base");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/script.lox"), "print \"loaded\";\n").unwrap();
+    fs::write(dir.join("lib.lox"), "var name = \"top\";\n").unwrap();
+    let (out, err) = session(
+        &dir,
+        ":load sub/script.lox\nimport \"lib.lox\" as lib;\nprint lib.name;\n",
+    );
+    assert!(out.contains("loaded"));
+    assert!(out.contains("top"), "{}", err);
+}